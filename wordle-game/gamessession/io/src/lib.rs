@@ -1,28 +1,82 @@
 #![no_std]
 
-use gmeta::{In, Out, InOut, Metadata};
+use gmeta::{In, InOut, Metadata};
 use gstd::{collections::HashMap, prelude::*, ActorId, MessageId};
+// The Wordle messages are the Wordle program's own types, so both sides always encode alike.
+pub use wordle_io::{Action as WordleAction, Event as WordleEvent, GameMode, Guess};
 
 pub struct GamesSessionMetadata;
 
 impl Metadata for GamesSessionMetadata {
     type Init = In<GamesSessionInit>;
-    type Handle = InOut<GamesSessionAction, GamesSessionResponse>;
-    type State = Out<GamesSessionState>;
+    type Handle = InOut<GamesSessionAction, Result<GamesSessionResponse, GamesSessionError>>;
+    type State = InOut<StateQuery, StateReply>;
     type Reply = ();
     type Others = ();
     type Signal = ();
 }
 
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub enum StateQuery {
+    All,
+    // Pages start at 0 and run from the oldest archived game to the newest.
+    ArchiveByUser {
+        user: ActorId,
+        page: u32,
+        page_size: u32,
+    },
+    // Games of every user that ended between `from` and `to`, both included, the earliest first.
+    ArchiveByBlocks {
+        from: u32,
+        to: u32,
+        page: u32,
+        page_size: u32,
+    },
+    // The best `top` players of a season and the rank of `user`, `None` is the current season.
+    Leaderboard {
+        season_id: Option<u32>,
+        top: u32,
+        user: ActorId,
+    },
+}
+
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub enum StateReply {
+    All(GamesSessionState),
+    Archive(Vec<(ActorId, ArchivedGame)>),
+    // `rank` starts at 1 and is `None` if the user has no points in the season.
+    Leaderboard {
+        season_id: u32,
+        top: Vec<(ActorId, u64)>,
+        rank: Option<(u32, u64)>,
+    },
+}
+
 #[derive(Debug, Default, Clone, Encode, Decode, TypeInfo)]
 pub struct GamesSessionState {
-    pub wordle_program_id: ActorId,
-    pub game_sessions: Vec<(ActorId, SessionInfo)>,
+    pub owner: ActorId,
+    pub backends: Vec<(u32, Backend)>,
+    pub config: SessionConfig,
+    pub game_sessions: Vec<((ActorId, u64), SessionInfo)>,
+    pub rooms: Vec<(u64, Room)>,
+    pub stats: Vec<(ActorId, PlayerStats)>,
+    pub season_id: u32,
+    pub season_started_at: u32,
+    pub entry_fee: u128,
+    pub rake_percent: u8,
+    pub prize_pool: u128,
+    pub rake_balance: u128,
+    pub session_keys: Vec<(ActorId, SessionKey)>,
 }
 
 #[derive(Debug, Default, Clone, Encode, Decode, TypeInfo)]
 pub struct GamesSessionInit {
     pub wordle_program_id: ActorId,
+    pub config: SessionConfig,
+    // Value to attach to `StartGame`, 0 makes games free.
+    pub entry_fee: u128,
+    // Percent of every entry fee kept by the owner instead of going to the prize pool.
+    pub rake_percent: u8,
 }
 
 impl GamesSessionInit {
@@ -31,44 +85,458 @@ impl GamesSessionInit {
             !self.wordle_program_id.is_zero(),
             "Invalid wordle_program_id"
         );
+        self.config.assert_valid();
+        assert!(self.rake_percent <= 100, "Invalid rake_percent");
     }
 }
 
 impl From<GamesSessionInit> for GamesSession {
     fn from(game_session_init: GamesSessionInit) -> Self {
+        let wordle = Backend {
+            program_id: game_session_init.wordle_program_id,
+            retired_program_ids: Vec::new(),
+            codec: Codec::Wordle,
+            occupied_by: None,
+        };
         Self {
-            wordle_program_id: game_session_init.wordle_program_id,
+            backends: [(WORDLE_GAME_TYPE, wordle)].into_iter().collect(),
+            config: game_session_init.config,
+            entry_fee: game_session_init.entry_fee,
+            rake_percent: game_session_init.rake_percent,
             ..Default::default()
         }
     }
 }
 
+// The Wordle program given at init is registered under this game type.
+pub const WORDLE_GAME_TYPE: u32 = 0;
+
+// A game program sessions can be played against.
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub struct Backend {
+    pub program_id: ActorId,
+    // Programs replaced by an upgrade, kept until their last game has ended.
+    pub retired_program_ids: Vec<ActorId>,
+    pub codec: Codec,
+    // The session using a backend that holds a single game.
+    pub occupied_by: Option<(ActorId, u64)>,
+}
+
+// How requests to a backend are encoded and its replies decoded.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum Codec {
+    Wordle,
+    // The pebble program holds one game, restarted with this setup for every session.
+    Pebbles {
+        difficulty: DifficultyLevel,
+        pebbles_count: u32,
+        max_pebbles_per_turn: u32,
+    },
+}
+
+impl Codec {
+    pub fn holds_single_game(&self) -> bool {
+        matches!(self, Codec::Pebbles { .. })
+    }
+}
+
+// A reply of a backend, decoded by its codec.
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub enum BackendEvent {
+    Wordle(WordleEvent),
+    // `None` is the empty reply to a restart.
+    Pebbles(Option<PebblesEvent>),
+}
+
+// The Wordle program refuses more tries than this whatever a session allows.
+pub const MAX_TRIES_LIMIT: u8 = 6;
+
+// Defaults for every game and the bounds a player can override them within.
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub struct SessionConfig {
+    pub tries_limit: u8,
+    pub min_tries_limit: u8,
+    // At most `MAX_TRIES_LIMIT`.
+    pub max_tries_limit: u8,
+    // Blocks after the start before an unfinished game is lost.
+    pub timeout_blocks: u32,
+    pub min_timeout_blocks: u32,
+    pub max_timeout_blocks: u32,
+    // Blocks to wait for a reply of the backend before waiting again or giving up.
+    pub reply_timeout_blocks: u32,
+    // Times the reply is waited for again, the request is sent only once.
+    pub max_rewaits: u8,
+    // The longest a session key can be valid for.
+    pub max_session_key_blocks: u32,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            tries_limit: 5,
+            min_tries_limit: 1,
+            max_tries_limit: 6,
+            // 200 blocks is about 10 minutes.
+            timeout_blocks: 200,
+            min_timeout_blocks: 20,
+            max_timeout_blocks: 28_800,
+            reply_timeout_blocks: 10,
+            max_rewaits: 2,
+            // About a day.
+            max_session_key_blocks: 28_800,
+        }
+    }
+}
+
+impl SessionConfig {
+    pub fn assert_valid(&self) {
+        assert!(
+            0 < self.min_tries_limit
+                && self.min_tries_limit <= self.tries_limit
+                && self.tries_limit <= self.max_tries_limit
+                && self.max_tries_limit <= MAX_TRIES_LIMIT,
+            "Invalid tries limit"
+        );
+        assert!(
+            0 < self.min_timeout_blocks
+                && self.min_timeout_blocks <= self.timeout_blocks
+                && self.timeout_blocks <= self.max_timeout_blocks,
+            "Invalid timeout"
+        );
+        assert!(self.reply_timeout_blocks > 0, "Invalid reply timeout");
+    }
+
+    pub fn tries_limit(&self, tries_limit: Option<u8>) -> Result<u8, GamesSessionError> {
+        let tries_limit = tries_limit.unwrap_or(self.tries_limit);
+        if !(self.min_tries_limit..=self.max_tries_limit).contains(&tries_limit) {
+            return Err(GamesSessionError::TriesLimitOutOfBounds);
+        }
+        Ok(tries_limit)
+    }
+
+    pub fn timeout_blocks(&self, timeout_blocks: Option<u32>) -> Result<u32, GamesSessionError> {
+        let timeout_blocks = timeout_blocks.unwrap_or(self.timeout_blocks);
+        if !(self.min_timeout_blocks..=self.max_timeout_blocks).contains(&timeout_blocks) {
+            return Err(GamesSessionError::TimeoutOutOfBounds);
+        }
+        Ok(timeout_blocks)
+    }
+}
+
 #[derive(Debug, Clone, Encode, Decode, TypeInfo)]
 pub enum GamesSessionAction {
-    StartGame,
+    // `game_id` is picked by the user, so several games can be open at once.
+    // `None` falls back to the defaults of the program's `SessionConfig`.
+    StartGame {
+        game_type: u32,
+        game_id: u64,
+        mode: GameMode,
+        hard_mode: bool,
+        tries_limit: Option<u8>,
+        timeout_blocks: Option<u32>,
+    },
     CheckWord {
+        game_id: u64,
         word: String,
     },
     CheckGameStatus {
         user: ActorId,
+        game_id: u64,
         session_id: MessageId,
     },
+    // Opens a race room that players can join before `start_block`.
+    CreateRoom {
+        start_block: u32,
+    },
+    JoinRoom {
+        room_id: u64,
+    },
+    // Ends the game as `Lose` and reveals its word.
+    GiveUp {
+        game_id: u64,
+    },
+    // Freezes the rankings of the current season and starts the next one, owner only.
+    EndSeason,
+    // Owner only.
+    SetRake {
+        rake_percent: u8,
+    },
+    // Sends part of the collected rake to the owner.
+    Withdraw {
+        amount: u128,
+    },
+    // The move of a pebbles game.
+    TakePebbles {
+        game_id: u64,
+        pebbles: u32,
+    },
+    // Owner only.
+    RegisterBackend {
+        game_type: u32,
+        program_id: ActorId,
+        codec: Codec,
+    },
+    // Owner only. New games go to `program_id`, games in progress finish on the old program.
+    UpgradeBackend {
+        game_type: u32,
+        program_id: ActorId,
+    },
+    // Owner only. Ends a game left on a retired program and refunds its entry fee.
+    EndSession {
+        user: ActorId,
+        game_id: u64,
+    },
+    // Offers `key` to make the given kinds of moves for the sender until `duration_blocks` have
+    // passed. The key makes no moves for the sender before it has accepted the offer.
+    CreateSessionKey {
+        key: ActorId,
+        duration_blocks: u32,
+        actions: Vec<ActionKind>,
+    },
+    // Sent by the owner or by the key itself.
+    RevokeSessionKey {
+        key: ActorId,
+    },
+    // Reveals a letter of the word in its position. A hint uses up a try in a free game and
+    // costs `SessionInfo::hint_price` in a game with an entry fee.
+    Hint {
+        game_id: u64,
+    },
+    // Sent by the key account to agree to make moves for `owner`.
+    AcceptSessionKey {
+        owner: ActorId,
+    },
+}
+
+// The actions a session key can be allowed to send.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum ActionKind {
+    StartGame,
+    CheckWord,
+    GiveUp,
+    TakePebbles,
+    Hint,
 }
 
+// A key that makes moves for `owner`, so they need not be signed by the owner's account.
 #[derive(Debug, Clone, Encode, Decode, TypeInfo)]
-pub enum WordleAction {
-    StartGame { user: ActorId },
-    CheckWord { user: ActorId, word: String },
+pub struct SessionKey {
+    pub owner: ActorId,
+    pub expires_at: u32,
+    pub actions: Vec<ActionKind>,
+    pub accepted: bool,
 }
 
 #[derive(Debug, Clone, Encode, Decode, TypeInfo)]
 pub enum GamesSessionResponse {
-    StartSuccess,
+    // `commitment` is the hash of the secret word and a salt, both revealed in `GameOver`.
+    StartSuccess {
+        commitment: [u8; 32],
+    },
     CheckWordResult {
         correct_positions: Vec<u8>,
         contained_in_word: Vec<u8>,
+        keyboard: [LetterStatus; 26],
     },
-    GameOver(GameStatus),
+    // `commitment` is the one of `StartSuccess`, which `word` and `salt` are checked against, or
+    // `bank` and `salt` in an Absurdle game.
+    GameOver {
+        game_id: u64,
+        status: GameStatus,
+        word: String,
+        salt: [u8; 32],
+        tries: u8,
+        board: Vec<Guess>,
+        bank: Vec<String>,
+        commitment: [u8; 32],
+    },
+    // The guess ignores hints revealed earlier in hard mode, no try was used.
+    HardModeViolation,
+    RoomCreated {
+        room_id: u64,
+    },
+    RoomJoined {
+        room_id: u64,
+    },
+    // Sent to every participant of a race room.
+    RoomUpdate(RoomEvent),
+    // The backend did not reply in time, the last action can be retried. A move may still be
+    // made by the backend, so its result is sent once the reply comes and the next move waits
+    // for it.
+    ReplyTimeout,
+    // The backend failed to handle the last action, the session is back where it was before.
+    BackendFailed(BackendError),
+    SeasonEnded {
+        season_id: u32,
+    },
+    // Sent with the prize of a win as its value.
+    PrizePaid {
+        amount: u128,
+    },
+    RakeUpdated {
+        rake_percent: u8,
+    },
+    Withdrawn {
+        amount: u128,
+    },
+    // The pebble program replies nothing to a restart, even when it moves first.
+    PebblesStarted,
+    PebblesTaken {
+        program_took: u32,
+    },
+    PebblesOver {
+        game_id: u64,
+        status: GameStatus,
+        tries: u8,
+    },
+    BackendRegistered {
+        game_type: u32,
+    },
+    BackendUpgraded {
+        game_type: u32,
+        program_id: ActorId,
+    },
+    // Sent to the user with the refunded entry fee as its value.
+    SessionEnded {
+        game_id: u64,
+    },
+    // The message waiting for the backend failed, so the session was set back. Sent with the
+    // refunded entry fee if the game had not started yet.
+    RequestLost {
+        game_id: u64,
+    },
+    SessionKeyCreated {
+        key: ActorId,
+        expires_at: u32,
+    },
+    SessionKeyRevoked {
+        key: ActorId,
+    },
+    // `letter` is the ASCII code of the letter at `position`.
+    HintRevealed {
+        position: u8,
+        letter: u8,
+        keyboard: [LetterStatus; 26],
+    },
+    SessionKeyAccepted {
+        owner: ActorId,
+    },
+}
+
+// Why a call was rejected, replied as `Err` instead of panicking.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum GamesSessionError {
+    NotOwner,
+    WrongEntryFee,
+    TriesLimitOutOfBounds,
+    TimeoutOutOfBounds,
+    GameTypeNotRegistered,
+    GameTypeAlreadyRegistered,
+    // The backend cannot play the requested mode, or the action does not fit its game.
+    WrongGameType,
+    BackendBusy,
+    SameProgram,
+    NotOnRetiredProgram,
+    GameInProgress,
+    NotInGame,
+    WaitingForBackend,
+    InvalidWord,
+    RoomNotFound,
+    NotInRoom,
+    AlreadyInRoom,
+    StartBlockPassed,
+    RaceNotStarted,
+    RaceAlreadyStarted,
+    RaceAlreadyPlayed,
+    InvalidRake,
+    NotEnoughRake,
+    // The key is the sender itself or a session key of another account.
+    InvalidSessionKey,
+    SessionKeyDurationOutOfBounds,
+    SessionKeyNotFound,
+    SessionKeyExpired,
+    ActionNotAllowed,
+    // Absurdle games have no hints, and a free game needs a try left for a guess after the hint.
+    HintNotAvailable,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum BackendError {
+    NotTrusted,
+    NoGame,
+    NoTriesLeft,
+    DailyAlreadyPlayed,
+    InvalidWord,
+    Unknown,
+    // A pebbles move out of the allowed range.
+    InvalidMove,
+    NoHiddenLetters,
+}
+
+impl BackendError {
+    // Backends report failures by panicking, so the panic messages of each codec are matched here.
+    pub fn from_panic_message(codec: &Codec, message: &str) -> Self {
+        match codec {
+            Codec::Wordle => Self::from_wordle_panic(message),
+            Codec::Pebbles { .. } => Self::from_pebbles_panic(message),
+        }
+    }
+
+    fn from_wordle_panic(message: &str) -> Self {
+        if message.contains("Only trusted programs can drive games") {
+            BackendError::NotTrusted
+        } else if message.contains("There is no game with this user") {
+            BackendError::NoGame
+        } else if message.contains("The user has no tries left") {
+            BackendError::NoTriesLeft
+        } else if message.contains("The user has already played the daily word") {
+            BackendError::DailyAlreadyPlayed
+        } else if message.contains("The length of the word exceeds 5") {
+            BackendError::InvalidWord
+        } else if message.contains("Every letter is already revealed") {
+            BackendError::NoHiddenLetters
+        } else {
+            BackendError::Unknown
+        }
+    }
+
+    fn from_pebbles_panic(message: &str) -> Self {
+        // "Remainig" is how the pebble program spells it.
+        if message.contains("Invalid input") || message.contains("Remainig is not enough") {
+            BackendError::InvalidMove
+        } else {
+            BackendError::Unknown
+        }
+    }
+}
+
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub enum RoomEvent {
+    PlayerJoined {
+        room_id: u64,
+        player: ActorId,
+    },
+    PlayerFinished {
+        room_id: u64,
+        player: ActorId,
+        status: GameStatus,
+        tries: u8,
+    },
+    RaceWon {
+        room_id: u64,
+        player: ActorId,
+        tries: u8,
+    },
+}
+
+// The best known status of a letter across every guess of the game, ordered from worst to best.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Encode, Decode, TypeInfo)]
+pub enum LetterStatus {
+    #[default]
+    Unknown,
+    Absent,
+    Present,
+    Correct,
 }
 
 #[derive(Debug, Clone, Encode, Decode, TypeInfo)]
@@ -78,47 +546,108 @@ pub enum GameStatus {
 }
 
 #[derive(Debug, Clone, Encode, Decode, TypeInfo)]
-pub enum WordleEvent {
-    GameStarted {
-        user: ActorId,
-    },
-    WordChecked {
-        user: ActorId,
-        correct_positions: Vec<u8>,
-        contained_in_word: Vec<u8>,
+pub enum PebblesAction {
+    Turn(u32),
+    GiveUp,
+    Restart {
+        difficulty: DifficultyLevel,
+        pebbles_count: u32,
+        max_pebbles_per_turn: u32,
     },
 }
 
-impl WordleEvent {
-    pub fn get_user(&self) -> &ActorId {
-        match self {
-            WordleEvent::GameStarted { user } => user,
-            WordleEvent::WordChecked { user, .. } => user,
-        }
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub enum PebblesEvent {
+    CounterTurn(u32),
+    Won(Player),
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum DifficultyLevel {
+    #[default]
+    Easy,
+    Hard,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum Player {
+    #[default]
+    User,
+    Program,
+}
+
+// The conversions below return `None` for a reply that does not answer the request, which the
+// session reports as `BackendError::Unknown`.
+pub fn has_guessed(wordle_event: &WordleEvent) -> Option<bool> {
+    match wordle_event {
+        WordleEvent::WordChecked {
+            correct_positions, ..
+        } => Some(correct_positions == &vec![0, 1, 2, 3, 4]),
+        _ => None,
     }
+}
 
-    pub fn has_guessed(&self) -> bool {
-        match self {
-            WordleEvent::GameStarted { .. } => unimplemented!(),
-            WordleEvent::WordChecked {
-                correct_positions, ..
-            } => correct_positions == &vec![0, 1, 2, 3, 4],
+impl GamesSessionResponse {
+    pub fn start_success(backend_event: &BackendEvent) -> Option<Self> {
+        match backend_event {
+            BackendEvent::Wordle(WordleEvent::GameStarted { commitment, .. }) => {
+                Some(GamesSessionResponse::StartSuccess {
+                    commitment: *commitment,
+                })
+            }
+            BackendEvent::Pebbles(None) => Some(GamesSessionResponse::PebblesStarted),
+            _ => None,
         }
     }
-}
 
-impl From<&WordleEvent> for GamesSessionResponse {
-    fn from(wordle_event: &WordleEvent) -> Self {
+    pub fn check_word_result(
+        wordle_event: &WordleEvent,
+        keyboard: [LetterStatus; 26],
+    ) -> Option<Self> {
         match wordle_event {
-            WordleEvent::GameStarted { .. } => GamesSessionResponse::StartSuccess,
             WordleEvent::WordChecked {
                 correct_positions,
                 contained_in_word,
                 ..
-            } => GamesSessionResponse::CheckWordResult {
+            } => Some(GamesSessionResponse::CheckWordResult {
                 correct_positions: correct_positions.clone(),
                 contained_in_word: contained_in_word.clone(),
-            },
+                keyboard,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn game_over(
+        game_id: u64,
+        status: GameStatus,
+        tries: u8,
+        commitment: [u8; 32],
+        backend_event: &BackendEvent,
+    ) -> Option<Self> {
+        match backend_event {
+            BackendEvent::Wordle(WordleEvent::WordRevealed {
+                word,
+                salt,
+                board,
+                bank,
+                ..
+            }) => Some(GamesSessionResponse::GameOver {
+                game_id,
+                status,
+                word: word.clone(),
+                salt: *salt,
+                tries,
+                board: board.clone(),
+                bank: bank.clone(),
+                commitment,
+            }),
+            BackendEvent::Pebbles(..) => Some(GamesSessionResponse::PebblesOver {
+                game_id,
+                status,
+                tries,
+            }),
+            BackendEvent::Wordle(..) => None,
         }
     }
 }
@@ -128,18 +657,53 @@ pub enum SessionStatus {
     #[default]
     Init,
     WaitUserInput,
-    WaitWordleStartReply,
-    WaitWordleCheckWordReply,
-    ReplyReceived(WordleEvent),
+    WaitBackendStartReply,
+    WaitBackendMoveReply,
+    // The game has ended with the given status and the word is being revealed.
+    WaitBackendRevealReply(GameStatus),
+    ReplyReceived(BackendEvent),
+    RevealReceived(GameStatus, BackendEvent),
     GameOver(GameStatus),
+    WaitBackendHintReply,
+    // The reply to the start request, the entry fee is taken once it has been handled.
+    StartReceived(BackendEvent),
+    // A move timed out but may still have been made, so its reply is taken when it comes.
+    WaitLateReply,
+    // A late reply ended the game, the word is revealed on the next action or the timeout check.
+    Finished(GameStatus),
 }
 
 #[derive(Default, Debug, Clone, Encode, Decode, TypeInfo)]
 pub struct SessionInfo {
+    pub game_type: u32,
+    // The backend program the game was started on.
+    pub program_id: ActorId,
     pub session_id: MessageId,
+    // Announced by the backend when the game started, zero for backends without one.
+    pub commitment: [u8; 32],
     pub original_msg_id: MessageId,
-    pub send_to_wordle_msg_id: MessageId,
+    pub send_to_backend_msg_id: MessageId,
     pub tries: u8,
+    pub started_at: u32,
+    pub tries_limit: u8,
+    pub timeout_blocks: u32,
+    // Times the reply to the pending request has been waited for again.
+    pub rewaits: u8,
+    // Paid to start the current game, refunded if it could not be started.
+    pub entry_fee: u128,
+    // Set when the backend fails on the pending request.
+    pub backend_error: Option<BackendError>,
+    pub mode: GameMode,
+    pub hard_mode: bool,
+    // The last word sent to the Wordle program, needed to colour the keyboard.
+    pub last_word: String,
+    // The last move sent to a pebbles backend.
+    pub last_pebbles: u32,
+    pub hints: u8,
+    // Paid for the pending hint, refunded if the letter could not be revealed.
+    pub hint_fee: u128,
+    // Indexed by letter, `keyboard[0]` is 'a'.
+    pub keyboard: [LetterStatus; 26],
     pub session_status: SessionStatus,
 }
 
@@ -147,26 +711,274 @@ impl SessionInfo {
     pub fn is_wait_reply_status(&self) -> bool {
         matches!(
             self.session_status,
-            SessionStatus::WaitWordleCheckWordReply | SessionStatus::WaitWordleStartReply
+            SessionStatus::WaitBackendMoveReply
+                | SessionStatus::WaitBackendStartReply
+                | SessionStatus::WaitBackendRevealReply(..)
+                | SessionStatus::WaitBackendHintReply
         )
     }
+
+    // A request to the backend is pending until the message that sent it has handled the reply.
+    pub fn is_pending(&self) -> bool {
+        self.is_wait_reply_status()
+            || matches!(
+                self.session_status,
+                SessionStatus::StartReceived(..)
+                    | SessionStatus::ReplyReceived(..)
+                    | SessionStatus::RevealReceived(..)
+            )
+    }
+
+    // Whether the event is the kind of reply the pending request gets, so that a backend answering
+    // with anything else is taken as a failed request.
+    pub fn is_answered_by(&self, backend_event: &BackendEvent) -> bool {
+        match (&self.session_status, backend_event) {
+            (SessionStatus::WaitBackendStartReply, BackendEvent::Wordle(event)) => {
+                matches!(event, WordleEvent::GameStarted { .. })
+            }
+            (
+                SessionStatus::WaitBackendMoveReply | SessionStatus::WaitLateReply,
+                BackendEvent::Wordle(event),
+            ) => matches!(
+                event,
+                WordleEvent::WordChecked { .. } | WordleEvent::HardModeViolation { .. }
+            ),
+            (SessionStatus::WaitBackendRevealReply(..), BackendEvent::Wordle(event)) => {
+                matches!(event, WordleEvent::WordRevealed { .. })
+            }
+            (SessionStatus::WaitBackendHintReply, BackendEvent::Wordle(event)) => {
+                matches!(event, WordleEvent::LetterRevealed { .. })
+            }
+            // The pebble program replies nothing to a restart, a turn or a win to a turn and the
+            // win of the program to a give up.
+            (SessionStatus::WaitBackendStartReply, BackendEvent::Pebbles(event)) => event.is_none(),
+            (
+                SessionStatus::WaitBackendMoveReply | SessionStatus::WaitLateReply,
+                BackendEvent::Pebbles(event),
+            ) => event.is_some(),
+            (SessionStatus::WaitBackendRevealReply(..), BackendEvent::Pebbles(event)) => {
+                matches!(event, Some(PebblesEvent::Won(..)))
+            }
+            _ => false,
+        }
+    }
+
+    pub fn is_in_progress(&self) -> bool {
+        !matches!(
+            self.session_status,
+            SessionStatus::Init | SessionStatus::GameOver(..)
+        )
+    }
+
+    // Hints are paid for with value when they have a price, otherwise with a try each.
+    pub fn tries_used(&self) -> u8 {
+        if self.hint_price() == 0 {
+            self.tries + self.hints
+        } else {
+            self.tries
+        }
+    }
+
+    // The entry fee spread over the tries of the game, nothing if that rounds down to 0.
+    pub fn hint_price(&self) -> u128 {
+        self.entry_fee / self.tries_limit as u128
+    }
+}
+
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub struct ArchivedGame {
+    pub game_id: u64,
+    pub game_type: u32,
+    pub session_id: MessageId,
+    pub started_at: u32,
+    pub ended_at: u32,
+    pub status: GameStatus,
+    pub tries: u8,
+    pub hints: u8,
+    // `None` if the Wordle program did not reveal the word.
+    pub word: Option<String>,
+    pub board: Vec<Guess>,
+}
+
+#[derive(Default, Debug, Clone, Encode, Decode, TypeInfo)]
+pub struct PlayerStats {
+    pub games_played: u32,
+    pub games_won: u32,
+    pub current_streak: u32,
+    pub max_streak: u32,
+    // `guess_distribution[i]` is the number of games won in `i + 1` tries.
+    pub guess_distribution: Vec<u32>,
+}
+
+impl PlayerStats {
+    pub fn record(&mut self, game_status: &GameStatus, tries: u8, tries_limit: u8) {
+        self.games_played += 1;
+        match game_status {
+            GameStatus::Win => {
+                self.games_won += 1;
+                self.current_streak += 1;
+                self.max_streak = self.max_streak.max(self.current_streak);
+                // Buckets run from 1 to the tries limit.
+                let buckets = tries_limit.max(tries) as usize;
+                if self.guess_distribution.len() < buckets {
+                    self.guess_distribution.resize(buckets, 0);
+                }
+                self.guess_distribution[tries.max(1) as usize - 1] += 1;
+            }
+            GameStatus::Lose => self.current_streak = 0,
+        }
+    }
+
+    pub fn win_percentage(&self) -> u8 {
+        (self.games_won * 100)
+            .checked_div(self.games_played)
+            .unwrap_or(0) as u8
+    }
+}
+
+// Points for a win: fewer tries and fewer blocks since the start score more, hard mode adds half.
+// A hint costs as many points as a try.
+pub fn score(tries: u8, hints: u8, tries_limit: u8, elapsed_blocks: u32, hard_mode: bool) -> u64 {
+    let points = 100
+        + tries_limit.saturating_sub(tries.saturating_add(hints)) as u64 * 20
+        + 50u64.saturating_sub(elapsed_blocks as u64 / 4);
+    if hard_mode {
+        points + points / 2
+    } else {
+        points
+    }
+}
+
+// Percent of the entry fee paid for a win in 1, 2, ... tries.
+pub const PAYOUT_CURVE: [u128; 6] = [500, 300, 200, 150, 120, 100];
+
+pub fn payout(entry_fee: u128, tries: u8) -> u128 {
+    let percent = PAYOUT_CURVE
+        .get(tries.max(1) as usize - 1)
+        .copied()
+        .unwrap_or(100);
+    entry_fee * percent / 100
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct Season {
+    pub id: u32,
+    pub started_at: u32,
+    pub points: HashMap<ActorId, u64>,
+}
+
+impl Season {
+    // Sorted by points, ties go to the lower ActorId so the order does not depend on the map.
+    pub fn rankings(&self) -> Vec<(ActorId, u64)> {
+        let mut rankings: Vec<(ActorId, u64)> = self
+            .points
+            .iter()
+            .map(|(user, points)| (*user, *points))
+            .collect();
+        rankings.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        rankings
+    }
+}
+
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub struct SeasonResult {
+    pub id: u32,
+    pub started_at: u32,
+    pub ended_at: u32,
+    pub rankings: Vec<(ActorId, u64)>,
+}
+
+#[derive(Default, Debug, Clone, Encode, Decode, TypeInfo)]
+pub struct Room {
+    pub host: ActorId,
+    pub start_block: u32,
+    pub players: Vec<ActorId>,
+    pub started: Vec<ActorId>,
+    pub winner: Option<RaceResult>,
+}
+
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub struct RaceResult {
+    pub player: ActorId,
+    pub tries: u8,
+    pub finished_at: u32,
+}
+
+impl Room {
+    // The first player to solve the word wins, a tie within one block goes to fewer tries.
+    pub fn record_win(&mut self, player: ActorId, tries: u8, finished_at: u32) -> bool {
+        let takes_lead = match &self.winner {
+            None => true,
+            Some(winner) => winner.finished_at == finished_at && tries < winner.tries,
+        };
+        if takes_lead {
+            self.winner = Some(RaceResult {
+                player,
+                tries,
+                finished_at,
+            });
+        }
+        takes_lead
+    }
 }
 
 #[derive(Default, Debug, Clone)]
 pub struct GamesSession {
-    pub wordle_program_id: ActorId,
-    pub sessions: HashMap<ActorId, SessionInfo>,
+    pub owner: ActorId,
+    pub backends: HashMap<u32, Backend>,
+    pub config: SessionConfig,
+    // Games being started or played, a finished game is only kept in `archive`.
+    pub sessions: HashMap<(ActorId, u64), SessionInfo>,
+    pub rooms: HashMap<u64, Room>,
+    pub next_room_id: u64,
+    pub stats: HashMap<ActorId, PlayerStats>,
+    pub archive: HashMap<ActorId, Vec<ArchivedGame>>,
+    pub season: Season,
+    pub seasons: Vec<SeasonResult>,
+    pub entry_fee: u128,
+    pub rake_percent: u8,
+    pub prize_pool: u128,
+    pub rake_balance: u128,
+    // Keyed by the key itself. An expired key is removed the next time it sends a move.
+    pub session_keys: HashMap<ActorId, SessionKey>,
 }
 
 impl From<&GamesSession> for GamesSessionState {
     fn from(game_session: &GamesSession) -> Self {
         Self {
-            wordle_program_id: game_session.wordle_program_id,
+            owner: game_session.owner,
+            backends: game_session
+                .backends
+                .iter()
+                .map(|(k, v)| (*k, v.clone()))
+                .collect(),
+            config: game_session.config.clone(),
             game_sessions: game_session
                 .sessions
                 .iter()
                 .map(|(k, v)| (*k, v.clone()))
                 .collect(),
+            rooms: game_session
+                .rooms
+                .iter()
+                .map(|(k, v)| (*k, v.clone()))
+                .collect(),
+            stats: game_session
+                .stats
+                .iter()
+                .map(|(k, v)| (*k, v.clone()))
+                .collect(),
+            season_id: game_session.season.id,
+            season_started_at: game_session.season.started_at,
+            entry_fee: game_session.entry_fee,
+            rake_percent: game_session.rake_percent,
+            prize_pool: game_session.prize_pool,
+            rake_balance: game_session.rake_balance,
+            session_keys: game_session
+                .session_keys
+                .iter()
+                .map(|(k, v)| (*k, v.clone()))
+                .collect(),
         }
     }
 }
\ No newline at end of file
@@ -1,9 +1,22 @@
 #![no_std]
 
 use gamessession_io::*;
-use gstd::{exec, msg};
+use gstd::{collections::HashMap, exec, msg, prelude::*, ActorId, MessageId, ReservationId};
 
-const TRIES_LIMIT: u8 = 5;
+// Finished games kept per user, the oldest one is dropped first.
+const ARCHIVE_LIMIT: usize = 32;
+// Gas kept for handle_signal by every message waiting for a backend.
+const SYSTEM_RESERVE_GAS: u64 = 5_000_000_000;
+// Gas reserved for each delayed CheckGameStatus.
+const CHECK_GAME_STATUS_GAS: u64 = 10_000_000_000;
+
+// What a session asks its backend for, encoded by the backend's codec.
+enum Request {
+    Start,
+    Move,
+    End,
+    Hint,
+}
 
 static mut GAMES_SESSION_STATE: Option<GamesSession> = None;
 
@@ -28,7 +41,14 @@ extern fn init() {
     let game_session_init: GamesSessionInit = msg::load().expect("Unable to decode GamesSessionInit");
     game_session_init.assert_valid();
     unsafe {
-        GAMES_SESSION_STATE = Some(game_session_init.into());
+        GAMES_SESSION_STATE = Some(GamesSession {
+            owner: msg::source(),
+            season: Season {
+                started_at: exec::block_height(),
+                ..Default::default()
+            },
+            ..game_session_init.into()
+        });
     };
 }
 
@@ -37,141 +57,1461 @@ extern fn handle() {
     let game_session_action: GamesSessionAction =
         msg::load().expect("Unable to decode GamesSessionAction");
     let game_session = get_game_session_mut();
+    // The value of a rejected call is sent back with the error.
+    if let Err(error) = process_action(game_session, game_session_action) {
+        msg::reply(Err::<GamesSessionResponse, _>(error), msg::value())
+            .expect("Error in sending a reply");
+    }
+}
+
+fn process_action(
+    game_session: &mut GamesSession,
+    game_session_action: GamesSessionAction,
+) -> Result<(), GamesSessionError> {
     match game_session_action {
         // Action 1
-        GamesSessionAction::StartGame => {
-            let user = msg::source();
+        GamesSessionAction::StartGame {
+            game_type,
+            game_id,
+            mode,
+            hard_mode,
+            tries_limit,
+            timeout_blocks,
+        } => {
+            let user = player(game_session, ActionKind::StartGame)?;
             // The program checks if a game already exists for the user;
-            let session_info = game_session.sessions.entry(user).or_default();
-            match &session_info.session_status {
-                SessionStatus::ReplyReceived(wordle_event) => {
-                    // A reply is sent to notify the user that the game has beeen successfully started.
-                    msg::reply::<GamesSessionResponse>(wordle_event.into(), 0)
-                        .expect("Error in sending a reply");
-                    session_info.session_status = SessionStatus::WaitUserInput;
+            if let Some(session_info) = game_session.sessions.get_mut(&(user, game_id)) {
+                check_not_pending(session_info)?;
+                match &session_info.session_status {
+                    SessionStatus::StartReceived(backend_event) => {
+                        let Some(response) = GamesSessionResponse::start_success(backend_event)
+                        else {
+                            let waiting = SessionStatus::WaitBackendStartReply;
+                            reject_reply(game_session, user, game_id, waiting);
+                            return Ok(());
+                        };
+                        // The fee only goes to the pool and the rake once the game has started.
+                        let rake = session_info.entry_fee * game_session.rake_percent as u128 / 100;
+                        game_session.rake_balance += rake;
+                        game_session.prize_pool += session_info.entry_fee - rake;
+                        // A reply is sent to notify the user that the game has beeen successfully started.
+                        reply(response, 0);
+                        session_info.session_status = SessionStatus::WaitUserInput;
+                        return Ok(());
+                    }
+                    SessionStatus::WaitBackendStartReply
+                        if msg::id() == session_info.original_msg_id =>
+                    {
+                        handle_failed_request(game_session, user, game_id);
+                        return Ok(());
+                    }
+                    SessionStatus::Init | SessionStatus::GameOver(..) => {}
+                    SessionStatus::WaitUserInput
+                    | SessionStatus::WaitBackendStartReply
+                    | SessionStatus::WaitBackendMoveReply
+                    | SessionStatus::WaitBackendRevealReply(..)
+                    | SessionStatus::WaitBackendHintReply
+                    | SessionStatus::ReplyReceived(..)
+                    | SessionStatus::RevealReceived(..)
+                    | SessionStatus::WaitLateReply
+                    | SessionStatus::Finished(..) => {
+                        return Err(GamesSessionError::GameInProgress);
+                    }
                 }
-                SessionStatus::Init
-                | SessionStatus::GameOver(..)
-                | SessionStatus::WaitWordleStartReply => {
-                    // It sends a "StartGame" message to the Wordle program;
-                    let send_to_wordle_msg_id = msg::send(
-                        game_session.wordle_program_id,
-                        WordleAction::StartGame { user },
-                        0,
-                    )
-                    .expect("Error in sending a message");
-
-                    session_info.session_id = msg::id();
-                    session_info.original_msg_id = msg::id();
-                    session_info.send_to_wordle_msg_id = send_to_wordle_msg_id;
-                    session_info.tries = 0;
-                    session_info.session_status = SessionStatus::WaitWordleStartReply;
-                    // Sends a delayed message with action CheckGameStatus to monitor the game's progress (its logic will be described below);
-                    // Specify a delay equal to 200 blocks (10 minutes) for the delayed message.
-                    msg::send_delayed(
-                        exec::program_id(),
-                        GamesSessionAction::CheckGameStatus {
-                            user,
-                            session_id: msg::id(),
-                        },
-                        0,
-                        200,
-                    )
-                    .expect("Error in send_delayed a message");
-                    // Utilizes the exec::wait() or exec::wait_for() function to await a response;
-                    exec::wait();
+            }
+            if msg::value() != game_session.entry_fee {
+                return Err(GamesSessionError::WrongEntryFee);
+            }
+            let tries_limit = game_session.config.tries_limit(tries_limit)?;
+            let timeout_blocks = game_session.config.timeout_blocks(timeout_blocks)?;
+            let backend = game_session
+                .backends
+                .get_mut(&game_type)
+                .ok_or(GamesSessionError::GameTypeNotRegistered)?;
+            if let Codec::Pebbles { .. } = backend.codec {
+                // The pebble program only has the classic mode.
+                if !matches!(mode, GameMode::Classic) || hard_mode {
+                    return Err(GamesSessionError::WrongGameType);
                 }
-                SessionStatus::WaitUserInput | SessionStatus::WaitWordleCheckWordReply => {
-                    panic!("The user is in the game");
+            }
+            // A backend holding a single game serves one session at a time.
+            if backend.codec.holds_single_game()
+                && backend
+                    .occupied_by
+                    .is_some_and(|session_key| session_key != (user, game_id))
+            {
+                return Err(GamesSessionError::BackendBusy);
+            }
+            // A race can be started once by each player of the room after its start block;
+            if let GameMode::Race { room_id } = mode {
+                let room = game_session
+                    .rooms
+                    .get_mut(&room_id)
+                    .ok_or(GamesSessionError::RoomNotFound)?;
+                if !room.players.contains(&user) {
+                    return Err(GamesSessionError::NotInRoom);
+                }
+                if exec::block_height() < room.start_block {
+                    return Err(GamesSessionError::RaceNotStarted);
+                }
+                if room.started.contains(&user) {
+                    return Err(GamesSessionError::RaceAlreadyPlayed);
                 }
+                room.started.push(user);
             }
+            if backend.codec.holds_single_game() {
+                backend.occupied_by = Some((user, game_id));
+            }
+            let program_id = backend.program_id;
+            // Only a start that passed every check creates the session.
+            let session_info = game_session.sessions.entry((user, game_id)).or_default();
+            session_info.game_type = game_type;
+            session_info.program_id = program_id;
+            session_info.session_id = msg::id();
+            session_info.original_msg_id = msg::id();
+            session_info.tries = 0;
+            session_info.hints = 0;
+            session_info.started_at = exec::block_height();
+            session_info.rewaits = 0;
+            session_info.entry_fee = msg::value();
+            session_info.tries_limit = tries_limit;
+            session_info.timeout_blocks = timeout_blocks;
+            session_info.mode = mode;
+            session_info.hard_mode = hard_mode;
+            session_info.keyboard = Default::default();
+            session_info.commitment = [0; 32];
+            // It sends a "StartGame" message to the Wordle program;
+            session_info.send_to_backend_msg_id =
+                send_request(&game_session.backends, user, session_info, Request::Start);
+            session_info.session_status = SessionStatus::WaitBackendStartReply;
+            // Sends a delayed message with action CheckGameStatus to monitor the game's progress (its logic will be described below);
+            // The delay is the timeout of the game, 200 blocks (10 minutes) by default.
+            schedule_check(user, game_id, msg::id(), timeout_blocks);
+            // Utilizes the exec::wait() or exec::wait_for() function to await a response;
+            wait_for_reply(game_session.config.reply_timeout_blocks);
         }
         // Action 2
-        GamesSessionAction::CheckWord { word } => {
-            let user = msg::source();
-            let session_info = game_session.sessions.entry(user).or_default();
+        GamesSessionAction::CheckWord { game_id, word } => {
+            let user = player(game_session, ActionKind::CheckWord)?;
+            let session_info = game_session
+                .sessions
+                .get_mut(&(user, game_id))
+                .ok_or(GamesSessionError::NotInGame)?;
+            if game_session.backends[&session_info.game_type].codec != Codec::Wordle {
+                return Err(GamesSessionError::WrongGameType);
+            }
+            check_not_pending(session_info)?;
             match &session_info.session_status {
-                SessionStatus::ReplyReceived(wordle_event) => {
+                SessionStatus::WaitBackendMoveReply | SessionStatus::WaitBackendRevealReply(..)
+                    if msg::id() == session_info.original_msg_id =>
+                {
+                    handle_failed_request(game_session, user, game_id);
+                }
+                // In hard mode a guess that ignores revealed hints is rejected without using up a try.
+                SessionStatus::ReplyReceived(BackendEvent::Wordle(
+                    WordleEvent::HardModeViolation { .. },
+                )) => {
+                    reply(GamesSessionResponse::HardModeViolation, 0);
+                    session_info.session_status = SessionStatus::WaitUserInput;
+                }
+                // The word of the finished game has been revealed, so the game is over.
+                SessionStatus::RevealReceived(..) => close_game(game_session, user, game_id),
+                SessionStatus::ReplyReceived(BackendEvent::Wordle(wordle_event)) => {
+                    let mut keyboard = session_info.keyboard;
+                    update_keyboard(&mut keyboard, &session_info.last_word, wordle_event);
+                    let (Some(guessed), Some(response)) = (
+                        has_guessed(wordle_event),
+                        GamesSessionResponse::check_word_result(wordle_event, keyboard),
+                    ) else {
+                        let waiting = SessionStatus::WaitBackendMoveReply;
+                        reject_reply(game_session, user, game_id, waiting);
+                        return Ok(());
+                    };
                     // increments the number of tries
                     session_info.tries += 1;
+                    session_info.keyboard = keyboard;
                     // and checks if the word was guessed.
-                    if wordle_event.has_guessed() {
-                        // If the word has been guessed, it switches the game status to GameOver(Win).
-                        session_info.session_status = SessionStatus::GameOver(GameStatus::Win);
-                        msg::reply(GamesSessionResponse::GameOver(GameStatus::Win), 0)
-                            .expect("Error in sending a reply");
-                    } else if session_info.tries == TRIES_LIMIT {
-                        // If all attempts are used up and the word is not guessed, it switches the game status to GameOver(Lose).
-                        session_info.session_status = SessionStatus::GameOver(GameStatus::Lose);
-                        msg::reply(GamesSessionResponse::GameOver(GameStatus::Lose), 0)
-                            .expect("Error in sending a reply");
+                    if guessed {
+                        // If the word has been guessed, the game ends with Win once the word is revealed.
+                        reveal_word(
+                            &game_session.backends,
+                            game_session.config.reply_timeout_blocks,
+                            user,
+                            session_info,
+                            GameStatus::Win,
+                        );
+                    } else if session_info.tries_used() == session_info.tries_limit {
+                        // If all attempts are used up and the word is not guessed, the game ends with Lose.
+                        reveal_word(
+                            &game_session.backends,
+                            game_session.config.reply_timeout_blocks,
+                            user,
+                            session_info,
+                            GameStatus::Lose,
+                        );
                     } else {
-                        msg::reply::<GamesSessionResponse>(wordle_event.into(), 0)
-                            .expect("Error in sending a reply");
+                        reply(response, 0);
                         session_info.session_status = SessionStatus::WaitUserInput;
                     }
                 }
+                // A move after the timeout ends the game instead.
+                SessionStatus::WaitUserInput if is_timed_out(session_info) => reveal_word(
+                    &game_session.backends,
+                    game_session.config.reply_timeout_blocks,
+                    user,
+                    session_info,
+                    GameStatus::Lose,
+                ),
                 // Ensures that a game exists and is in the correct status;
-                SessionStatus::WaitUserInput | SessionStatus::WaitWordleCheckWordReply => {
+                SessionStatus::WaitUserInput => {
                     // Validates that the submitted word length is five and is in lowercase;
-                    assert!(
-                        word.len() == 5 && word.chars().all(|c| c.is_lowercase()),
-                        "Invalid word"
-                    );
+                    // only ASCII letters pass, as the keyboard is indexed by byte.
+                    if word.len() != 5 || !word.bytes().all(|b| b.is_ascii_lowercase()) {
+                        return Err(GamesSessionError::InvalidWord);
+                    }
+                    session_info.last_word = word;
                     // Sends a "CheckWord" message to the Wordle program;
-                    let send_to_wordle_msg_id = msg::send(
-                        game_session.wordle_program_id,
-                        WordleAction::CheckWord { user, word },
-                        0,
-                    )
-                    .expect("Error in sending a message");
+                    session_info.send_to_backend_msg_id =
+                        send_request(&game_session.backends, user, session_info, Request::Move);
                     session_info.original_msg_id = msg::id();
-                    session_info.send_to_wordle_msg_id = send_to_wordle_msg_id;
-                    session_info.session_status = SessionStatus::WaitWordleCheckWordReply;
+                    session_info.rewaits = 0;
+                    session_info.session_status = SessionStatus::WaitBackendMoveReply;
                     // Utilizes the exec::wait() or exec::wait_for() function to await a reply;
-                    exec::wait();
+                    wait_for_reply(game_session.config.reply_timeout_blocks);
+                }
+                // The game was ended by a late reply, so this action reveals its word.
+                SessionStatus::Finished(game_status) => {
+                    let game_status = game_status.clone();
+                    reveal_word(
+                        &game_session.backends,
+                        game_session.config.reply_timeout_blocks,
+                        user,
+                        session_info,
+                        game_status,
+                    );
+                }
+                // Pebbles sessions are rejected above.
+                SessionStatus::ReplyReceived(BackendEvent::Pebbles(..)) => unreachable!(),
+                SessionStatus::WaitBackendStartReply
+                | SessionStatus::WaitBackendMoveReply
+                | SessionStatus::WaitBackendRevealReply(..)
+                | SessionStatus::WaitBackendHintReply
+                | SessionStatus::StartReceived(..)
+                | SessionStatus::WaitLateReply => {
+                    return Err(GamesSessionError::WaitingForBackend);
                 }
-                SessionStatus::Init
-                | SessionStatus::WaitWordleStartReply
-                | SessionStatus::GameOver(..) => {
-                    panic!("The user is not in the game");
+                SessionStatus::Init | SessionStatus::GameOver(..) => {
+                    return Err(GamesSessionError::NotInGame);
                 }
             }
         }
         // Action 3
-        GamesSessionAction::CheckGameStatus { user, session_id } => {
+        GamesSessionAction::CheckGameStatus {
+            user,
+            game_id,
+            session_id,
+        } => {
             if msg::source() == exec::program_id() {
-                if let Some(session_info) = game_session.sessions.get_mut(&user) {
-                    if session_id == session_info.session_id
-                        && !matches!(session_info.session_status, SessionStatus::GameOver(..))
+                if let Some(session_info) = game_session.sessions.get_mut(&(user, game_id)) {
+                    if session_id == session_info.session_id {
+                        match &session_info.session_status {
+                            // Woken up once the word of the timed out game has been revealed.
+                            SessionStatus::RevealReceived(..)
+                                if msg::id() == session_info.original_msg_id =>
+                            {
+                                close_game(game_session, user, game_id);
+                            }
+                            SessionStatus::WaitBackendRevealReply(..)
+                                if msg::id() == session_info.original_msg_id =>
+                            {
+                                handle_failed_request(game_session, user, game_id);
+                            }
+                            // A session back in `Init` never got its game started.
+                            SessionStatus::Init
+                            | SessionStatus::WaitBackendRevealReply(..)
+                            | SessionStatus::RevealReceived(..)
+                            | SessionStatus::GameOver(..) => {}
+                            // A move still waiting for its late reply is lost with the game.
+                            SessionStatus::WaitUserInput | SessionStatus::WaitLateReply => {
+                                reveal_word(
+                                    &game_session.backends,
+                                    game_session.config.reply_timeout_blocks,
+                                    user,
+                                    session_info,
+                                    GameStatus::Lose,
+                                )
+                            }
+                            SessionStatus::Finished(game_status) => {
+                                let game_status = game_status.clone();
+                                reveal_word(
+                                    &game_session.backends,
+                                    game_session.config.reply_timeout_blocks,
+                                    user,
+                                    session_info,
+                                    game_status,
+                                );
+                            }
+                            // Another request is pending, so the check waits for it to be
+                            // answered or to time out. Waiting only pays the waitlist rent, so
+                            // the gas of the reservation lasts, and no new request can start
+                            // once the game is past its timeout.
+                            SessionStatus::WaitBackendStartReply
+                            | SessionStatus::WaitBackendMoveReply
+                            | SessionStatus::WaitBackendHintReply
+                            | SessionStatus::StartReceived(..)
+                            | SessionStatus::ReplyReceived(..) => {
+                                exec::wait_for(game_session.config.reply_timeout_blocks);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        // Action 4
+        GamesSessionAction::CreateRoom { start_block } => {
+            let host = msg::source();
+            if start_block <= exec::block_height() {
+                return Err(GamesSessionError::StartBlockPassed);
+            }
+            let room_id = game_session.next_room_id;
+            game_session.next_room_id += 1;
+            game_session.rooms.insert(
+                room_id,
+                Room {
+                    host,
+                    start_block,
+                    players: vec![host],
+                    ..Default::default()
+                },
+            );
+            reply(GamesSessionResponse::RoomCreated { room_id }, 0);
+        }
+        // Action 5
+        GamesSessionAction::JoinRoom { room_id } => {
+            let player = msg::source();
+            let room = game_session
+                .rooms
+                .get_mut(&room_id)
+                .ok_or(GamesSessionError::RoomNotFound)?;
+            if exec::block_height() >= room.start_block {
+                return Err(GamesSessionError::RaceAlreadyStarted);
+            }
+            if room.players.contains(&player) {
+                return Err(GamesSessionError::AlreadyInRoom);
+            }
+            broadcast(room, RoomEvent::PlayerJoined { room_id, player });
+            room.players.push(player);
+            reply(GamesSessionResponse::RoomJoined { room_id }, 0);
+        }
+        // Action 6
+        // Ends the game as lost straight away, the pending CheckGameStatus then finds it over.
+        GamesSessionAction::GiveUp { game_id } => {
+            let user = player(game_session, ActionKind::GiveUp)?;
+            let session_info = game_session
+                .sessions
+                .get_mut(&(user, game_id))
+                .ok_or(GamesSessionError::NotInGame)?;
+            match &session_info.session_status {
+                SessionStatus::RevealReceived(..) if msg::id() == session_info.original_msg_id => {
+                    close_game(game_session, user, game_id);
+                }
+                SessionStatus::WaitBackendRevealReply(..)
+                    if msg::id() == session_info.original_msg_id =>
+                {
+                    handle_failed_request(game_session, user, game_id);
+                }
+                // Revealing the word also makes the Wordle program drop the game.
+                SessionStatus::WaitUserInput => reveal_word(
+                    &game_session.backends,
+                    game_session.config.reply_timeout_blocks,
+                    user,
+                    session_info,
+                    GameStatus::Lose,
+                ),
+                // The game was ended by a late reply, so this action reveals its word.
+                SessionStatus::Finished(game_status) => {
+                    let game_status = game_status.clone();
+                    reveal_word(
+                        &game_session.backends,
+                        game_session.config.reply_timeout_blocks,
+                        user,
+                        session_info,
+                        game_status,
+                    );
+                }
+                SessionStatus::Init | SessionStatus::GameOver(..) => {
+                    return Err(GamesSessionError::NotInGame);
+                }
+                _ => return Err(GamesSessionError::WaitingForBackend),
+            }
+        }
+        // Action 7
+        GamesSessionAction::EndSeason => {
+            check_owner(game_session)?;
+            let season_id = game_session.season.id;
+            game_session.seasons.push(SeasonResult {
+                id: season_id,
+                started_at: game_session.season.started_at,
+                ended_at: exec::block_height(),
+                rankings: game_session.season.rankings(),
+            });
+            game_session.season = Season {
+                id: season_id + 1,
+                started_at: exec::block_height(),
+                ..Default::default()
+            };
+            reply(GamesSessionResponse::SeasonEnded { season_id }, 0);
+        }
+        // Action 8
+        GamesSessionAction::SetRake { rake_percent } => {
+            check_owner(game_session)?;
+            if rake_percent > 100 {
+                return Err(GamesSessionError::InvalidRake);
+            }
+            game_session.rake_percent = rake_percent;
+            reply(GamesSessionResponse::RakeUpdated { rake_percent }, 0);
+        }
+        // Action 9
+        GamesSessionAction::Withdraw { amount } => {
+            check_owner(game_session)?;
+            if amount > game_session.rake_balance {
+                return Err(GamesSessionError::NotEnoughRake);
+            }
+            game_session.rake_balance -= amount;
+            reply(GamesSessionResponse::Withdrawn { amount }, amount);
+        }
+        // Action 10
+        // Mirrors CheckWord for the backends playing pebbles.
+        GamesSessionAction::TakePebbles { game_id, pebbles } => {
+            let user = player(game_session, ActionKind::TakePebbles)?;
+            let session_info = game_session
+                .sessions
+                .get_mut(&(user, game_id))
+                .ok_or(GamesSessionError::NotInGame)?;
+            if !matches!(
+                game_session.backends[&session_info.game_type].codec,
+                Codec::Pebbles { .. }
+            ) {
+                return Err(GamesSessionError::WrongGameType);
+            }
+            check_not_pending(session_info)?;
+            match &session_info.session_status {
+                SessionStatus::WaitBackendMoveReply | SessionStatus::WaitBackendRevealReply(..)
+                    if msg::id() == session_info.original_msg_id =>
+                {
+                    handle_failed_request(game_session, user, game_id);
+                }
+                SessionStatus::RevealReceived(..) => close_game(game_session, user, game_id),
+                // The move ended the game, there is no word to reveal.
+                SessionStatus::ReplyReceived(BackendEvent::Pebbles(Some(PebblesEvent::Won(
+                    player,
+                )))) => {
+                    let game_status = match player {
+                        Player::User => GameStatus::Win,
+                        Player::Program => GameStatus::Lose,
+                    };
+                    let backend_event =
+                        BackendEvent::Pebbles(Some(PebblesEvent::Won(player.clone())));
+                    session_info.tries += 1;
+                    session_info.session_status =
+                        SessionStatus::RevealReceived(game_status, backend_event);
+                    close_game(game_session, user, game_id);
+                }
+                SessionStatus::ReplyReceived(BackendEvent::Pebbles(Some(
+                    PebblesEvent::CounterTurn(program_took),
+                ))) => {
+                    let program_took = *program_took;
+                    session_info.tries += 1;
+                    if session_info.tries == session_info.tries_limit {
+                        // Giving up makes the pebble program end its game.
+                        reveal_word(
+                            &game_session.backends,
+                            game_session.config.reply_timeout_blocks,
+                            user,
+                            session_info,
+                            GameStatus::Lose,
+                        );
+                    } else {
+                        reply(GamesSessionResponse::PebblesTaken { program_took }, 0);
+                        session_info.session_status = SessionStatus::WaitUserInput;
+                    }
+                }
+                // A move after the timeout ends the game instead.
+                SessionStatus::WaitUserInput if is_timed_out(session_info) => reveal_word(
+                    &game_session.backends,
+                    game_session.config.reply_timeout_blocks,
+                    user,
+                    session_info,
+                    GameStatus::Lose,
+                ),
+                SessionStatus::WaitUserInput => {
+                    session_info.last_pebbles = pebbles;
+                    session_info.send_to_backend_msg_id =
+                        send_request(&game_session.backends, user, session_info, Request::Move);
+                    session_info.original_msg_id = msg::id();
+                    session_info.rewaits = 0;
+                    session_info.session_status = SessionStatus::WaitBackendMoveReply;
+                    wait_for_reply(game_session.config.reply_timeout_blocks);
+                }
+                // The game was ended by a late reply, so this action reveals its word.
+                SessionStatus::Finished(game_status) => {
+                    let game_status = game_status.clone();
+                    reveal_word(
+                        &game_session.backends,
+                        game_session.config.reply_timeout_blocks,
+                        user,
+                        session_info,
+                        game_status,
+                    );
+                }
+                SessionStatus::Init | SessionStatus::GameOver(..) => {
+                    return Err(GamesSessionError::NotInGame);
+                }
+                _ => return Err(GamesSessionError::WaitingForBackend),
+            }
+        }
+        // Action 11
+        GamesSessionAction::RegisterBackend {
+            game_type,
+            program_id,
+            codec,
+        } => {
+            check_owner(game_session)?;
+            if game_session.backends.contains_key(&game_type) {
+                return Err(GamesSessionError::GameTypeAlreadyRegistered);
+            }
+            game_session.backends.insert(
+                game_type,
+                Backend {
+                    program_id,
+                    retired_program_ids: Vec::new(),
+                    codec,
+                    occupied_by: None,
+                },
+            );
+            reply(GamesSessionResponse::BackendRegistered { game_type }, 0);
+        }
+        // Action 12
+        // The new program has to trust this program before games can be started on it.
+        GamesSessionAction::UpgradeBackend {
+            game_type,
+            program_id,
+        } => {
+            check_owner(game_session)?;
+            let backend = game_session
+                .backends
+                .get_mut(&game_type)
+                .ok_or(GamesSessionError::GameTypeNotRegistered)?;
+            if backend.program_id == program_id {
+                return Err(GamesSessionError::SameProgram);
+            }
+            // The game of a single game backend cannot be moved to another program.
+            if backend.occupied_by.is_some() {
+                return Err(GamesSessionError::BackendBusy);
+            }
+            let old_program_id = backend.program_id;
+            backend.program_id = program_id;
+            backend.retired_program_ids.retain(|id| *id != program_id);
+            backend.retired_program_ids.push(old_program_id);
+            release_retired(game_session, game_type, old_program_id);
+            reply(
+                GamesSessionResponse::BackendUpgraded {
+                    game_type,
+                    program_id,
+                },
+                0,
+            );
+        }
+        // Action 13
+        GamesSessionAction::EndSession { user, game_id } => {
+            check_owner(game_session)?;
+            let session_info = game_session
+                .sessions
+                .get_mut(&(user, game_id))
+                .ok_or(GamesSessionError::NotInGame)?;
+            let backend = &game_session.backends[&session_info.game_type];
+            if !backend
+                .retired_program_ids
+                .contains(&session_info.program_id)
+            {
+                return Err(GamesSessionError::NotOnRetiredProgram);
+            }
+            match session_info.session_status {
+                SessionStatus::WaitUserInput => {}
+                SessionStatus::Init | SessionStatus::GameOver(..) => {
+                    return Err(GamesSessionError::NotInGame);
+                }
+                _ => return Err(GamesSessionError::WaitingForBackend),
+            }
+            // The game never counts, so the fee goes back from the pool as far as it can.
+            let refund = session_info.entry_fee.min(game_session.prize_pool);
+            game_session.prize_pool -= refund;
+            if let GameMode::Race { room_id } = session_info.mode {
+                if let Some(room) = game_session.rooms.get_mut(&room_id) {
+                    room.started.retain(|player| *player != user);
+                }
+            }
+            let (game_type, program_id) = (session_info.game_type, session_info.program_id);
+            game_session.sessions.remove(&(user, game_id));
+            release_retired(game_session, game_type, program_id);
+            notify(user, GamesSessionResponse::SessionEnded { game_id }, refund);
+            reply(GamesSessionResponse::SessionEnded { game_id }, 0);
+        }
+        // Action 14
+        GamesSessionAction::CreateSessionKey {
+            key,
+            duration_blocks,
+            actions,
+        } => {
+            let owner = msg::source();
+            let block_height = exec::block_height();
+            let live_key = |account: &ActorId| {
+                game_session
+                    .session_keys
+                    .get(account)
+                    .filter(|session_key| block_height < session_key.expires_at)
+            };
+            // A key cannot hand its rights on, and a live key of another account is not taken over.
+            if key == owner
+                || live_key(&owner).is_some_and(|session_key| session_key.accepted)
+                || live_key(&key).is_some_and(|session_key| session_key.owner != owner)
+            {
+                return Err(GamesSessionError::InvalidSessionKey);
+            }
+            if duration_blocks == 0 || duration_blocks > game_session.config.max_session_key_blocks
+            {
+                return Err(GamesSessionError::SessionKeyDurationOutOfBounds);
+            }
+            let expires_at = block_height + duration_blocks;
+            game_session.session_keys.insert(
+                key,
+                SessionKey {
+                    owner,
+                    expires_at,
+                    actions,
+                    accepted: false,
+                },
+            );
+            reply(
+                GamesSessionResponse::SessionKeyCreated { key, expires_at },
+                0,
+            );
+        }
+        // Action 15
+        GamesSessionAction::RevokeSessionKey { key } => {
+            if !game_session
+                .session_keys
+                .get(&key)
+                .is_some_and(|session_key| {
+                    session_key.owner == msg::source() || key == msg::source()
+                })
+            {
+                return Err(GamesSessionError::SessionKeyNotFound);
+            }
+            // A move the key sent before still finishes, it is found by its pending request.
+            game_session.session_keys.remove(&key);
+            reply(GamesSessionResponse::SessionKeyRevoked { key }, 0);
+        }
+        // Action 16
+        GamesSessionAction::Hint { game_id } => {
+            let user = player(game_session, ActionKind::Hint)?;
+            let session_info = game_session
+                .sessions
+                .get_mut(&(user, game_id))
+                .ok_or(GamesSessionError::NotInGame)?;
+            if game_session.backends[&session_info.game_type].codec != Codec::Wordle {
+                return Err(GamesSessionError::WrongGameType);
+            }
+            check_not_pending(session_info)?;
+            match &session_info.session_status {
+                SessionStatus::WaitBackendHintReply | SessionStatus::WaitBackendRevealReply(..)
+                    if msg::id() == session_info.original_msg_id =>
+                {
+                    handle_failed_request(game_session, user, game_id);
+                }
+                SessionStatus::RevealReceived(..) => close_game(game_session, user, game_id),
+                SessionStatus::ReplyReceived(BackendEvent::Wordle(
+                    WordleEvent::LetterRevealed {
+                        position, letter, ..
+                    },
+                )) => {
+                    let (position, letter) = (*position, *letter);
+                    // The fee of the hint is shared like an entry fee once the letter is revealed.
+                    let hint_fee = session_info.hint_fee;
+                    session_info.hint_fee = 0;
+                    let rake = hint_fee * game_session.rake_percent as u128 / 100;
+                    game_session.rake_balance += rake;
+                    game_session.prize_pool += hint_fee - rake;
+                    session_info.hints += 1;
+                    session_info.keyboard[(letter - b'a') as usize] = LetterStatus::Correct;
+                    reply(
+                        GamesSessionResponse::HintRevealed {
+                            position,
+                            letter,
+                            keyboard: session_info.keyboard,
+                        },
+                        0,
+                    );
+                    session_info.session_status = SessionStatus::WaitUserInput;
+                }
+                // A move after the timeout ends the game instead.
+                SessionStatus::WaitUserInput if is_timed_out(session_info) => reveal_word(
+                    &game_session.backends,
+                    game_session.config.reply_timeout_blocks,
+                    user,
+                    session_info,
+                    GameStatus::Lose,
+                ),
+                SessionStatus::WaitUserInput => {
+                    if session_info.mode == GameMode::Absurdle
+                        || (session_info.hint_price() == 0
+                            && session_info.tries_used() + 1 >= session_info.tries_limit)
                     {
-                        session_info.session_status = SessionStatus::GameOver(GameStatus::Lose);
-                        msg::send(user, GamesSessionResponse::GameOver(GameStatus::Lose), 0)
-                            .expect("Error in sending a reply");
+                        return Err(GamesSessionError::HintNotAvailable);
+                    }
+                    if msg::value() != session_info.hint_price() {
+                        return Err(GamesSessionError::WrongEntryFee);
                     }
+                    session_info.hint_fee = msg::value();
+                    session_info.send_to_backend_msg_id =
+                        send_request(&game_session.backends, user, session_info, Request::Hint);
+                    session_info.original_msg_id = msg::id();
+                    session_info.rewaits = 0;
+                    session_info.session_status = SessionStatus::WaitBackendHintReply;
+                    wait_for_reply(game_session.config.reply_timeout_blocks);
                 }
+                // The game was ended by a late reply, so this action reveals its word.
+                SessionStatus::Finished(game_status) => {
+                    let game_status = game_status.clone();
+                    reveal_word(
+                        &game_session.backends,
+                        game_session.config.reply_timeout_blocks,
+                        user,
+                        session_info,
+                        game_status,
+                    );
+                }
+                SessionStatus::Init | SessionStatus::GameOver(..) => {
+                    return Err(GamesSessionError::NotInGame);
+                }
+                _ => return Err(GamesSessionError::WaitingForBackend),
             }
         }
+        // Action 17
+        GamesSessionAction::AcceptSessionKey { owner } => {
+            let key = msg::source();
+            let session_key = game_session
+                .session_keys
+                .get_mut(&key)
+                .filter(|session_key| session_key.owner == owner)
+                .ok_or(GamesSessionError::SessionKeyNotFound)?;
+            if exec::block_height() >= session_key.expires_at {
+                game_session.session_keys.remove(&key);
+                return Err(GamesSessionError::SessionKeyExpired);
+            }
+            session_key.accepted = true;
+            reply(GamesSessionResponse::SessionKeyAccepted { owner }, 0);
+        }
     }
+    Ok(())
+}
+
+// The user a move is made for: the sender itself, or the owner of the accepted session key
+// that sent it.
+fn player(
+    game_session: &mut GamesSession,
+    action: ActionKind,
+) -> Result<ActorId, GamesSessionError> {
+    // A message woken up for its own pending request goes on for the user it was sent for, even
+    // if the key that sent it has expired or been revoked since.
+    if let Some(&(user, _)) = game_session
+        .sessions
+        .iter()
+        .find(|(_, session_info)| {
+            session_info.original_msg_id == msg::id() && session_info.is_pending()
+        })
+        .map(|(session_key, _)| session_key)
+    {
+        return Ok(user);
+    }
+    let source = msg::source();
+    let Some(session_key) = game_session
+        .session_keys
+        .get(&source)
+        .filter(|session_key| session_key.accepted)
+    else {
+        return Ok(source);
+    };
+    if exec::block_height() >= session_key.expires_at {
+        // The account makes its own moves again from now on.
+        game_session.session_keys.remove(&source);
+        return Err(GamesSessionError::SessionKeyExpired);
+    }
+    if !session_key.actions.contains(&action) {
+        return Err(GamesSessionError::ActionNotAllowed);
+    }
+    Ok(session_key.owner)
+}
+
+fn is_timed_out(session_info: &SessionInfo) -> bool {
+    exec::block_height() >= session_info.started_at + session_info.timeout_blocks
+}
+
+// Only the message that sent the pending request may move the session on, so a second request
+// cannot take over the session while the first one is still waiting.
+fn check_not_pending(session_info: &SessionInfo) -> Result<(), GamesSessionError> {
+    if session_info.is_pending() && msg::id() != session_info.original_msg_id {
+        return Err(GamesSessionError::WaitingForBackend);
+    }
+    Ok(())
+}
+
+fn check_owner(game_session: &GamesSession) -> Result<(), GamesSessionError> {
+    if msg::source() != game_session.owner {
+        return Err(GamesSessionError::NotOwner);
+    }
+    Ok(())
+}
+
+// Tells the user the revealed word of the finished game and closes the session.
+fn close_game(game_session: &mut GamesSession, user: ActorId, game_id: u64) {
+    let session_info = game_session
+        .sessions
+        .get_mut(&(user, game_id))
+        .expect("The user is not in the game");
+    let SessionStatus::RevealReceived(game_status, backend_event) =
+        session_info.session_status.clone()
+    else {
+        unreachable!()
+    };
+    let Some(response) = GamesSessionResponse::game_over(
+        game_id,
+        game_status.clone(),
+        session_info.tries,
+        session_info.commitment,
+        &backend_event,
+    ) else {
+        let waiting = SessionStatus::WaitBackendRevealReply(game_status);
+        reject_reply(game_session, user, game_id, waiting);
+        return;
+    };
+    respond(user, response, 0);
+    session_info.session_status = SessionStatus::GameOver(game_status.clone());
+    let session_info = session_info.clone();
+    finish_game(
+        game_session,
+        user,
+        game_id,
+        &session_info,
+        &game_status,
+        Some(&backend_event),
+    );
+}
+
+// The delayed CheckGameStatus handler has no user message to reply to.
+fn respond(user: ActorId, response: GamesSessionResponse, value: u128) {
+    if msg::source() == exec::program_id() {
+        notify(user, response, value);
+    } else {
+        reply(response, value);
+    }
+}
+
+// Everything the program sends to users decodes as the `Handle` output of the metadata.
+fn reply(response: GamesSessionResponse, value: u128) {
+    msg::reply(Ok::<_, GamesSessionError>(response), value).expect("Error in sending a reply");
+}
+
+fn notify(user: ActorId, response: GamesSessionResponse, value: u128) {
+    msg::send(user, Ok::<_, GamesSessionError>(response), value)
+        .expect("Error in sending a message");
+}
+
+// Bookkeeping shared by every way a game can end, `backend_event` is the reveal if there was one.
+fn finish_game(
+    game_session: &mut GamesSession,
+    user: ActorId,
+    game_id: u64,
+    session_info: &SessionInfo,
+    game_status: &GameStatus,
+    backend_event: Option<&BackendEvent>,
+) {
+    let (word, board) = match backend_event {
+        Some(BackendEvent::Wordle(WordleEvent::WordRevealed { word, board, .. })) => {
+            (Some(word.clone()), board.clone())
+        }
+        _ => (None, Vec::new()),
+    };
+    let backend = game_session
+        .backends
+        .get_mut(&session_info.game_type)
+        .expect("The game type is not registered");
+    if backend.occupied_by == Some((user, game_id)) {
+        backend.occupied_by = None;
+    }
+    // The payout, the season points and the stats are measured in Wordle tries, so the games of
+    // other backends are only archived.
+    let is_wordle = backend.codec == Codec::Wordle;
+    release_retired(
+        game_session,
+        session_info.game_type,
+        session_info.program_id,
+    );
+    if is_wordle && matches!(game_status, GameStatus::Win) {
+        // Games lost by timeout or by giving up leave their fee in the pool.
+        let amount =
+            payout(session_info.entry_fee, session_info.tries).min(game_session.prize_pool);
+        if amount > 0 {
+            game_session.prize_pool -= amount;
+            notify(user, GamesSessionResponse::PrizePaid { amount }, amount);
+        }
+        *game_session.season.points.entry(user).or_default() += score(
+            session_info.tries,
+            session_info.hints,
+            session_info.tries_limit,
+            exec::block_height() - session_info.started_at,
+            session_info.hard_mode,
+        );
+    }
+    let archive = game_session.archive.entry(user).or_default();
+    if archive.len() == ARCHIVE_LIMIT {
+        archive.remove(0);
+    }
+    archive.push(ArchivedGame {
+        game_id,
+        game_type: session_info.game_type,
+        session_id: session_info.session_id,
+        started_at: session_info.started_at,
+        ended_at: exec::block_height(),
+        status: game_status.clone(),
+        tries: session_info.tries,
+        hints: session_info.hints,
+        word,
+        board,
+    });
+    if is_wordle {
+        game_session.stats.entry(user).or_default().record(
+            game_status,
+            session_info.tries,
+            session_info.tries_limit,
+        );
+    }
+    if let GameMode::Race { room_id } = session_info.mode {
+        if let Some(room) = game_session.rooms.get_mut(&room_id) {
+            broadcast(
+                room,
+                RoomEvent::PlayerFinished {
+                    room_id,
+                    player: user,
+                    status: game_status.clone(),
+                    tries: session_info.tries,
+                },
+            );
+            if matches!(game_status, GameStatus::Win)
+                && room.record_win(user, session_info.tries, exec::block_height())
+            {
+                broadcast(
+                    room,
+                    RoomEvent::RaceWon {
+                        room_id,
+                        player: user,
+                        tries: session_info.tries,
+                    },
+                );
+            }
+        }
+    }
+    // The game lives on in the archive, so its session goes and the sessions scanned stay few.
+    game_session.sessions.remove(&(user, game_id));
+}
+
+// Upgrades the status of every letter of the checked word, a letter never gets a worse status.
+fn update_keyboard(keyboard: &mut [LetterStatus; 26], word: &str, wordle_event: &WordleEvent) {
+    if let WordleEvent::WordChecked {
+        correct_positions,
+        contained_in_word,
+        ..
+    } = wordle_event
+    {
+        for (i, letter) in word.bytes().enumerate() {
+            let status = if correct_positions.contains(&(i as u8)) {
+                LetterStatus::Correct
+            } else if contained_in_word.contains(&(i as u8)) {
+                LetterStatus::Present
+            } else {
+                LetterStatus::Absent
+            };
+            let key = &mut keyboard[(letter - b'a') as usize];
+            *key = (*key).max(status);
+        }
+    }
+}
+
+// Forgets a retired program once none of its games is in progress.
+fn release_retired(game_session: &mut GamesSession, game_type: u32, program_id: ActorId) {
+    let in_use = game_session.sessions.values().any(|session_info| {
+        session_info.game_type == game_type
+            && session_info.program_id == program_id
+            && session_info.is_in_progress()
+    });
+    if !in_use {
+        if let Some(backend) = game_session.backends.get_mut(&game_type) {
+            backend.retired_program_ids.retain(|id| *id != program_id);
+        }
+    }
+}
+
+fn broadcast(room: &Room, room_event: RoomEvent) {
+    for player in &room.players {
+        notify(
+            *player,
+            GamesSessionResponse::RoomUpdate(room_event.clone()),
+            0,
+        );
+    }
+}
+
+// Asks the Wordle program to reveal the word of a finished game and waits for the reply.
+fn reveal_word(
+    backends: &HashMap<u32, Backend>,
+    reply_timeout_blocks: u32,
+    user: ActorId,
+    session_info: &mut SessionInfo,
+    game_status: GameStatus,
+) -> ! {
+    session_info.send_to_backend_msg_id = send_request(backends, user, session_info, Request::End);
+    session_info.original_msg_id = msg::id();
+    session_info.rewaits = 0;
+    session_info.session_status = SessionStatus::WaitBackendRevealReply(game_status);
+    wait_for_reply(reply_timeout_blocks);
+}
+
+// The system reserve pays for handle_signal if the waiting message never gets to run again.
+fn wait_for_reply(reply_timeout_blocks: u32) -> ! {
+    exec::system_reserve_gas(SYSTEM_RESERVE_GAS).expect("Unable to reserve gas for a signal");
+    exec::wait_for(reply_timeout_blocks);
+}
+
+// The check is paid from a reservation, so it is sent even when the message scheduling it
+// runs low on gas.
+fn schedule_check(user: ActorId, game_id: u64, session_id: MessageId, delay: u32) {
+    // The reservation has to outlive the delay.
+    let reservation_id = ReservationId::reserve(CHECK_GAME_STATUS_GAS, delay + 1)
+        .expect("Unable to reserve gas for CheckGameStatus");
+    msg::send_delayed_from_reservation(
+        reservation_id,
+        exec::program_id(),
+        GamesSessionAction::CheckGameStatus {
+            user,
+            game_id,
+            session_id,
+        },
+        0,
+        delay,
+    )
+    .expect("Error in send_delayed a message");
+}
+
+// The Wordle program keys a game by the session that started it, so a reused `game_id` never
+// reaches the game of an earlier session.
+fn send_request(
+    backends: &HashMap<u32, Backend>,
+    user: ActorId,
+    session_info: &SessionInfo,
+    request: Request,
+) -> MessageId {
+    let backend = backends
+        .get(&session_info.game_type)
+        .expect("The game type is not registered");
+    let session_id = session_info.session_id;
+    let payload = match (&backend.codec, request) {
+        (Codec::Wordle, Request::Start) => WordleAction::StartGame {
+            user,
+            session_id,
+            mode: session_info.mode,
+            hard_mode: session_info.hard_mode,
+        }
+        .encode(),
+        (Codec::Wordle, Request::Move) => WordleAction::CheckWord {
+            user,
+            session_id,
+            word: session_info.last_word.clone(),
+        }
+        .encode(),
+        (Codec::Wordle, Request::End) => WordleAction::RevealWord { user, session_id }.encode(),
+        (Codec::Wordle, Request::Hint) => WordleAction::RevealLetter { user, session_id }.encode(),
+        (
+            Codec::Pebbles {
+                difficulty,
+                pebbles_count,
+                max_pebbles_per_turn,
+            },
+            Request::Start,
+        ) => PebblesAction::Restart {
+            difficulty: *difficulty,
+            pebbles_count: *pebbles_count,
+            max_pebbles_per_turn: *max_pebbles_per_turn,
+        }
+        .encode(),
+        (Codec::Pebbles { .. }, Request::Move) => {
+            PebblesAction::Turn(session_info.last_pebbles).encode()
+        }
+        (Codec::Pebbles { .. }, Request::End) => PebblesAction::GiveUp.encode(),
+        // Hints are only asked for in Wordle sessions.
+        (Codec::Pebbles { .. }, Request::Hint) => unreachable!(),
+    };
+    // A game started before an upgrade stays on the program it was started on.
+    msg::send_bytes(session_info.program_id, payload, 0).expect("Error in sending a message")
+}
+
+// A message waiting for the Wordle program wakes up with its status unchanged only when the Wordle
+// program failed or did not reply in time. Every message gets a reply in the end, so a late one
+// is waited for a few more times before the session falls back. Sending the request again could
+// make the backend handle it twice.
+fn handle_failed_request(game_session: &mut GamesSession, user: ActorId, game_id: u64) {
+    let session_info = game_session
+        .sessions
+        .get_mut(&(user, game_id))
+        .expect("The user is not in the game");
+    let backend_error = session_info.backend_error.clone();
+    if backend_error.is_none() && session_info.rewaits < game_session.config.max_rewaits {
+        session_info.rewaits += 1;
+        wait_for_reply(game_session.config.reply_timeout_blocks);
+    }
+
+    let response = match backend_error {
+        Some(backend_error) => GamesSessionResponse::BackendFailed(backend_error),
+        None => GamesSessionResponse::ReplyTimeout,
+    };
+    let refund = fall_back(game_session, user, game_id);
+    respond(user, response, refund);
+}
+
+// A reply that does not answer the pending request is handled like a failure of the backend,
+// `waiting` is the status the session had while waiting for it.
+fn reject_reply(
+    game_session: &mut GamesSession,
+    user: ActorId,
+    game_id: u64,
+    waiting: SessionStatus,
+) {
+    let session_info = game_session
+        .sessions
+        .get_mut(&(user, game_id))
+        .expect("The user is not in the game");
+    session_info.backend_error = Some(BackendError::Unknown);
+    session_info.session_status = waiting;
+    handle_failed_request(game_session, user, game_id);
+}
+
+// Moves a session whose request failed back to where the user can go on. Returns the entry fee
+// to refund, which is only kept when the game was started.
+fn fall_back(game_session: &mut GamesSession, user: ActorId, game_id: u64) -> u128 {
+    let session_info = game_session
+        .sessions
+        .get_mut(&(user, game_id))
+        .expect("The user is not in the game");
+    // A request the backend failed was not applied, one that timed out may still be.
+    let backend_failed = session_info.backend_error.take().is_some();
+    match session_info.session_status.clone() {
+        // No game was started, so the session goes. The race can be started again as well, and
+        // the backend is free for other sessions.
+        SessionStatus::WaitBackendStartReply => {
+            let refund = session_info.entry_fee;
+            if let Some(backend) = game_session.backends.get_mut(&session_info.game_type) {
+                if backend.occupied_by == Some((user, game_id)) {
+                    backend.occupied_by = None;
+                }
+            }
+            if let GameMode::Race { room_id } = session_info.mode {
+                if let Some(room) = game_session.rooms.get_mut(&room_id) {
+                    room.started.retain(|player| *player != user);
+                }
+            }
+            let (game_type, program_id) = (session_info.game_type, session_info.program_id);
+            game_session.sessions.remove(&(user, game_id));
+            release_retired(game_session, game_type, program_id);
+            return refund;
+        }
+        // The game has started, so its fee is taken as if the reply had been handled.
+        SessionStatus::StartReceived(..) => {
+            let entry_fee = session_info.entry_fee;
+            let rake = entry_fee * game_session.rake_percent as u128 / 100;
+            game_session.rake_balance += rake;
+            game_session.prize_pool += entry_fee - rake;
+            session_info.session_status = SessionStatus::WaitUserInput;
+        }
+        // The reply to the move is taken when it comes, so the session follows the backend.
+        SessionStatus::WaitBackendMoveReply if !backend_failed => {
+            session_info.session_status = SessionStatus::WaitLateReply;
+        }
+        // A reply that was never handled is dropped like a missing one, a hint that was not
+        // given is refunded.
+        SessionStatus::WaitBackendMoveReply
+        | SessionStatus::WaitBackendHintReply
+        | SessionStatus::ReplyReceived(..) => {
+            session_info.session_status = SessionStatus::WaitUserInput;
+            let refund = session_info.hint_fee;
+            session_info.hint_fee = 0;
+            return refund;
+        }
+        // The game is over even though its word could not be revealed.
+        SessionStatus::WaitBackendRevealReply(game_status)
+        | SessionStatus::RevealReceived(game_status, _) => {
+            session_info.session_status = SessionStatus::GameOver(game_status.clone());
+            let session_info = session_info.clone();
+            finish_game(
+                game_session,
+                user,
+                game_id,
+                &session_info,
+                &game_status,
+                None,
+            );
+        }
+        _ => {}
+    }
+    0
+}
+
+// Runs with the system reserve when a message waiting for a backend has failed or has been
+// removed from the waitlist, so its session does not stay pending forever.
+#[no_mangle]
+extern fn handle_signal() {
+    let signal_from = msg::signal_from().expect("Failed to query signal_from");
+    let game_session = get_game_session_mut();
+    let Some(&(user, game_id)) = game_session
+        .sessions
+        .iter()
+        .find(|(_, session_info)| {
+            session_info.original_msg_id == signal_from && session_info.is_pending()
+        })
+        .map(|(session_key, _)| session_key)
+    else {
+        return;
+    };
+    let refund = fall_back(game_session, user, game_id);
+    notify(user, GamesSessionResponse::RequestLost { game_id }, refund);
 }
 
 #[no_mangle]
 extern fn handle_reply() {
     let reply_to = msg::reply_to().expect("Failed to query reply_to data");
-    let wordle_event: WordleEvent = msg::load().expect("Unable to decode WordleEvent");
     let game_session = get_game_session_mut();
-    let user = wordle_event.get_user();
-    if let Some(session_info) = game_session.sessions.get_mut(user) {
-        if reply_to == session_info.send_to_wordle_msg_id && session_info.is_wait_reply_status() {
-            session_info.session_status = SessionStatus::ReplyReceived(wordle_event);
-            exec::wake(session_info.original_msg_id).expect("Failed to wake message");
+    let Some(backend) = game_session.backends.values().find(|backend| {
+        backend.program_id == msg::source() || backend.retired_program_ids.contains(&msg::source())
+    }) else {
+        return;
+    };
+    // A reply belongs to the session whose pending request it answers.
+    let Some((&(user, game_id), session_info)) =
+        game_session.sessions.iter_mut().find(|(_, session_info)| {
+            reply_to == session_info.send_to_backend_msg_id
+                && (session_info.is_wait_reply_status()
+                    || matches!(session_info.session_status, SessionStatus::WaitLateReply))
+        })
+    else {
+        return;
+    };
+    // An error reply carries the panic message of the backend instead of its event.
+    let reply = if msg::reply_code()
+        .expect("Failed to query reply_code")
+        .is_error()
+    {
+        let message = msg::load_bytes().expect("Failed to load the error reply");
+        Err(BackendError::from_panic_message(
+            &backend.codec,
+            &String::from_utf8_lossy(&message),
+        ))
+    } else {
+        let backend_event = match backend.codec {
+            Codec::Wordle => {
+                BackendEvent::Wordle(msg::load().expect("Unable to decode WordleEvent"))
+            }
+            Codec::Pebbles { .. } => {
+                let payload = msg::load_bytes().expect("Failed to load the reply");
+                let pebbles_event = if payload.is_empty() {
+                    None
+                } else {
+                    Some(
+                        PebblesEvent::decode(&mut payload.as_slice())
+                            .expect("Unable to decode PebblesEvent"),
+                    )
+                };
+                BackendEvent::Pebbles(pebbles_event)
+            }
+        };
+        if session_info.is_answered_by(&backend_event) {
+            Ok(backend_event)
+        } else {
+            Err(BackendError::Unknown)
+        }
+    };
+    // No message waits for the reply to a move that timed out.
+    if matches!(session_info.session_status, SessionStatus::WaitLateReply) {
+        catch_up(game_session, user, game_id, reply);
+        return;
+    }
+    match reply {
+        Ok(backend_event) => {
+            session_info.session_status = match &session_info.session_status {
+                SessionStatus::WaitBackendStartReply => {
+                    if let BackendEvent::Wordle(WordleEvent::GameStarted { commitment, .. }) =
+                        &backend_event
+                    {
+                        session_info.commitment = *commitment;
+                    }
+                    SessionStatus::StartReceived(backend_event)
+                }
+                SessionStatus::WaitBackendRevealReply(game_status) => {
+                    SessionStatus::RevealReceived(game_status.clone(), backend_event)
+                }
+                _ => SessionStatus::ReplyReceived(backend_event),
+            }
         }
+        Err(backend_error) => session_info.backend_error = Some(backend_error),
     }
+    exec::wake(session_info.original_msg_id).expect("Failed to wake message");
+}
+
+// Makes the move whose reply came after the session stopped waiting for it and tells the user.
+// A game the move ended is revealed by the next action or the timeout check, as no message
+// waiting for a reveal can be started here.
+fn catch_up(
+    game_session: &mut GamesSession,
+    user: ActorId,
+    game_id: u64,
+    reply: Result<BackendEvent, BackendError>,
+) {
+    let session_info = game_session
+        .sessions
+        .get_mut(&(user, game_id))
+        .expect("The user is not in the game");
+    session_info.session_status = SessionStatus::WaitUserInput;
+    let response = match reply {
+        Err(backend_error) => GamesSessionResponse::BackendFailed(backend_error),
+        Ok(BackendEvent::Wordle(WordleEvent::HardModeViolation { .. })) => {
+            GamesSessionResponse::HardModeViolation
+        }
+        Ok(BackendEvent::Wordle(wordle_event)) => {
+            let mut keyboard = session_info.keyboard;
+            update_keyboard(&mut keyboard, &session_info.last_word, &wordle_event);
+            let (Some(guessed), Some(response)) = (
+                has_guessed(&wordle_event),
+                GamesSessionResponse::check_word_result(&wordle_event, keyboard),
+            ) else {
+                unreachable!("The reply is checked to answer the move")
+            };
+            session_info.tries += 1;
+            session_info.keyboard = keyboard;
+            if guessed {
+                session_info.session_status = SessionStatus::Finished(GameStatus::Win);
+            } else if session_info.tries_used() == session_info.tries_limit {
+                session_info.session_status = SessionStatus::Finished(GameStatus::Lose);
+            }
+            response
+        }
+        Ok(BackendEvent::Pebbles(Some(PebblesEvent::Won(player)))) => {
+            session_info.tries += 1;
+            session_info.session_status = SessionStatus::Finished(match player {
+                Player::User => GameStatus::Win,
+                Player::Program => GameStatus::Lose,
+            });
+            return;
+        }
+        Ok(BackendEvent::Pebbles(Some(PebblesEvent::CounterTurn(program_took)))) => {
+            session_info.tries += 1;
+            if session_info.tries == session_info.tries_limit {
+                session_info.session_status = SessionStatus::Finished(GameStatus::Lose);
+            }
+            GamesSessionResponse::PebblesTaken { program_took }
+        }
+        Ok(BackendEvent::Pebbles(None)) => unreachable!("The reply is checked to answer the move"),
+    };
+    notify(user, response, 0);
 }
 
 #[no_mangle]
 extern fn state() {
+    let query: StateQuery = msg::load().expect("Unable to decode StateQuery");
     let game_session = get_game_session();
-    msg::reply::<GamesSessionState>(game_session.into(), 0)
-        .expect("failed to encode or reply from state()");
+    let reply = match query {
+        StateQuery::All => StateReply::All(game_session.into()),
+        StateQuery::ArchiveByUser {
+            user,
+            page,
+            page_size,
+        } => StateReply::Archive(paginate(
+            game_session
+                .archive
+                .get(&user)
+                .into_iter()
+                .flatten()
+                .map(|archived_game| (user, archived_game.clone())),
+            page,
+            page_size,
+        )),
+        StateQuery::ArchiveByBlocks {
+            from,
+            to,
+            page,
+            page_size,
+        } => {
+            let mut games: Vec<_> = game_session
+                .archive
+                .iter()
+                .flat_map(|(user, archive)| {
+                    archive
+                        .iter()
+                        .filter(|archived_game| (from..=to).contains(&archived_game.ended_at))
+                        .map(|archived_game| (*user, archived_game))
+                })
+                .collect();
+            // The archive map has no order, so pages are cut from the games sorted by their end.
+            games.sort_by_key(|(user, archived_game)| (archived_game.ended_at, *user));
+            StateReply::Archive(paginate(
+                games
+                    .into_iter()
+                    .map(|(user, archived_game)| (user, archived_game.clone())),
+                page,
+                page_size,
+            ))
+        }
+        StateQuery::Leaderboard {
+            season_id,
+            top,
+            user,
+        } => {
+            let (season_id, rankings) = match season_id {
+                // A season that never existed has an empty leaderboard.
+                Some(season_id) if season_id != game_session.season.id => {
+                    let rankings = game_session
+                        .seasons
+                        .iter()
+                        .find(|season| season.id == season_id)
+                        .map(|season| season.rankings.clone())
+                        .unwrap_or_default();
+                    (season_id, rankings)
+                }
+                _ => (game_session.season.id, game_session.season.rankings()),
+            };
+            let rank = rankings
+                .iter()
+                .position(|(player, _)| *player == user)
+                .map(|position| (position as u32 + 1, rankings[position].1));
+            StateReply::Leaderboard {
+                season_id,
+                top: rankings.into_iter().take(top as usize).collect(),
+                rank,
+            }
+        }
+    };
+    msg::reply(reply, 0).expect("failed to encode or reply from state()");
+}
+
+fn paginate<T>(items: impl Iterator<Item = T>, page: u32, page_size: u32) -> Vec<T> {
+    items
+        .skip(page as usize * page_size as usize)
+        .take(page_size as usize)
+        .collect()
 }
\ No newline at end of file
@@ -1,11 +1,152 @@
 use gamessession_io::*;
-use gtest::{Log, ProgramBuilder, System};
+use gstd::{ActorId, MessageId};
+use gtest::{Log, Program, ProgramBuilder, RunResult, System, WasmProgram};
+use parity_scale_codec::{Decode, Encode};
 
 const GAMES_SESSION_PROGRAM_ID: u64 = 1;
 const WORDLE_PROGRAM_ID: u64 = 2;
+const PEBBLES_PROGRAM_ID: u64 = 3;
+const NEW_WORDLE_PROGRAM_ID: u64 = 4;
+const SESSION_KEY: u64 = 60;
+const PEBBLES_GAME_TYPE: u32 = 1;
 // USER is my student number
 const USER: u64 = 50;
 
+// The start commitment and the revealed salt are random, so responses are matched by shape.
+fn contains_response(res: &RunResult, expected: impl Fn(&GamesSessionResponse) -> bool) -> bool {
+    res.decoded_log::<Result<GamesSessionResponse, GamesSessionError>>()
+        .iter()
+        .any(|log| matches!(log.payload(), Ok(response) if expected(response)))
+}
+
+// A rejected call is replied with an error instead of failing.
+fn contains_error(res: &RunResult, error: GamesSessionError) -> bool {
+    !res.main_failed()
+        && res
+            .decoded_log::<Result<GamesSessionResponse, GamesSessionError>>()
+            .iter()
+            .any(|log| matches!(log.payload(), Err(logged) if *logged == error))
+}
+
+// Keyboard after guessing "house" when only 'u' is not in the word.
+fn house_keyboard() -> [LetterStatus; 26] {
+    let mut keyboard = [LetterStatus::Unknown; 26];
+    for (i, letter) in "house".bytes().enumerate() {
+        keyboard[(letter - b'a') as usize] = if i == 2 {
+            LetterStatus::Absent
+        } else {
+            LetterStatus::Correct
+        };
+    }
+    keyboard
+}
+
+// Answers every request with a revealed word, which a session cannot take as the start of a game,
+// so the request fails as if the backend had.
+#[derive(Debug)]
+struct FaultyBackend;
+
+impl WasmProgram for FaultyBackend {
+    fn init(&mut self, _payload: Vec<u8>) -> Result<Option<Vec<u8>>, &'static str> {
+        Ok(None)
+    }
+
+    fn handle(&mut self, _payload: Vec<u8>) -> Result<Option<Vec<u8>>, &'static str> {
+        let wordle_event = WordleEvent::WordRevealed {
+            user: USER.into(),
+            session_id: MessageId::zero(),
+            word: "house".to_string(),
+            salt: [0; 32],
+            board: Vec::new(),
+            bank: Vec::new(),
+        };
+        Ok(Some(wordle_event.encode()))
+    }
+
+    fn handle_reply(&mut self, _payload: Vec<u8>) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    fn handle_signal(&mut self, _payload: Vec<u8>) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<u8>, &'static str> {
+        Ok(Vec::new())
+    }
+}
+
+// Plays pebbles like the pebble program when the user moves first and the program always takes
+// one pebble, so the tests do not need the wasm of a crate outside this workspace.
+#[derive(Debug, Default)]
+struct PebblesBackend {
+    max_pebbles_per_turn: u32,
+    pebbles_remaining: u32,
+}
+
+impl WasmProgram for PebblesBackend {
+    fn init(&mut self, _payload: Vec<u8>) -> Result<Option<Vec<u8>>, &'static str> {
+        Ok(None)
+    }
+
+    fn handle(&mut self, payload: Vec<u8>) -> Result<Option<Vec<u8>>, &'static str> {
+        let action = PebblesAction::decode(&mut payload.as_slice())
+            .map_err(|_| "Get PebbleAction Error.")?;
+        let pebbles_event = match action {
+            // Like the pebble program, a restart gets an empty reply.
+            PebblesAction::Restart {
+                pebbles_count,
+                max_pebbles_per_turn,
+                ..
+            } => {
+                self.pebbles_remaining = pebbles_count;
+                self.max_pebbles_per_turn = max_pebbles_per_turn;
+                return Ok(None);
+            }
+            PebblesAction::Turn(pebbles) => {
+                if pebbles == 0 || pebbles > self.max_pebbles_per_turn {
+                    return Err("Invalid input, please try again.");
+                }
+                if pebbles > self.pebbles_remaining {
+                    return Err("Remainig is not enough, please try again.");
+                }
+                self.pebbles_remaining -= pebbles;
+                if self.pebbles_remaining == 0 {
+                    PebblesEvent::Won(Player::User)
+                } else {
+                    self.pebbles_remaining -= 1;
+                    if self.pebbles_remaining == 0 {
+                        PebblesEvent::Won(Player::Program)
+                    } else {
+                        PebblesEvent::CounterTurn(1)
+                    }
+                }
+            }
+            PebblesAction::GiveUp => PebblesEvent::Won(Player::Program),
+        };
+        Ok(Some(pebbles_event.encode()))
+    }
+
+    fn handle_reply(&mut self, _payload: Vec<u8>) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    fn handle_signal(&mut self, _payload: Vec<u8>) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<u8>, &'static str> {
+        Ok(Vec::new())
+    }
+}
+
+fn read_state(program: &Program) -> GamesSessionState {
+    match program.read_state(StateQuery::All).unwrap() {
+        StateReply::All(state) => state,
+        _ => unreachable!(),
+    }
+}
+
 #[test]
 fn test_win() {
     let system = System::new();
@@ -23,12 +164,20 @@ fn test_win() {
     // Case 1: wordle_program init
     let res = wordle_program.send_bytes(USER, []);
     assert!(!res.main_failed());
+    let res = wordle_program.send(
+        USER,
+        wordle_io::Action::AddTrustedProgram {
+            program_id: GAMES_SESSION_PROGRAM_ID.into(),
+        },
+    );
+    assert!(!res.main_failed());
 
     // Case 2: gamessession_program init
     let res = gamessession_program.send(
         USER,
         GamesSessionInit {
             wordle_program_id: WORDLE_PROGRAM_ID.into(),
+            ..Default::default()
         },
     );
     assert!(!res.main_failed());
@@ -37,81 +186,292 @@ fn test_win() {
     let res = gamessession_program.send(
         USER,
         GamesSessionAction::CheckWord {
+            game_id: 0,
             word: "abcde".to_string(),
         },
     );
-    assert!(res.main_failed());
+    assert!(contains_error(&res, GamesSessionError::NotInGame));
 
     // Case 4: StartGame - success
-    let res = gamessession_program.send(USER, GamesSessionAction::StartGame);
-    let log = Log::builder()
-        .dest(USER)
-        .source(GAMES_SESSION_PROGRAM_ID)
-        .payload(GamesSessionResponse::StartSuccess);
-    assert!(!res.main_failed() && res.contains(&log));
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionAction::StartGame {
+            game_type: WORDLE_GAME_TYPE,
+            game_id: 0,
+            mode: GameMode::Classic,
+            hard_mode: false,
+            tries_limit: None,
+            timeout_blocks: None,
+        },
+    );
+    assert!(
+        !res.main_failed()
+            && contains_response(&res, |response| matches!(
+                response,
+                GamesSessionResponse::StartSuccess { .. }
+            ))
+    );
 
     // Case 5: StartGame failed: The user is aleady in the game
-    let res = gamessession_program.send(USER, GamesSessionAction::StartGame);
-    assert!(res.main_failed());
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionAction::StartGame {
+            game_type: WORDLE_GAME_TYPE,
+            game_id: 0,
+            mode: GameMode::Classic,
+            hard_mode: false,
+            tries_limit: None,
+            timeout_blocks: None,
+        },
+    );
+    assert!(contains_error(&res, GamesSessionError::GameInProgress));
 
     // Case 6: CheckWord failed: Invalid word
     let res = gamessession_program.send(
         USER,
         GamesSessionAction::CheckWord {
+            game_id: 0,
             word: "qwert".to_string(),
         },
     );
-    assert!(res.main_failed());
+    assert!(contains_error(&res, GamesSessionError::InvalidWord));
 
     // Case 7: CheckWord failed: Invalid word
     let res = gamessession_program.send(
         USER,
         GamesSessionAction::CheckWord {
+            game_id: 0,
             word: "shell".to_string(),
         },
     );
-    assert!(res.main_failed());
+    assert!(contains_error(&res, GamesSessionError::InvalidWord));
+    // "éabc" is five bytes long, but not five ASCII letters
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionAction::CheckWord {
+            game_id: 0,
+            word: "éabc".to_string(),
+        },
+    );
+    assert!(contains_error(&res, GamesSessionError::InvalidWord));
 
     // Case 8: CheckWord success, but failed to guess
     let res = gamessession_program.send(
         USER,
         GamesSessionAction::CheckWord {
+            game_id: 0,
             word: "house".to_string(),
         },
     );
     let log = Log::builder()
         .dest(USER)
         .source(GAMES_SESSION_PROGRAM_ID)
-        .payload(GamesSessionResponse::CheckWordResult {
-            correct_positions: vec![0, 1, 3, 4],
-            contained_in_word: vec![],
-        });
+        .payload(Ok::<_, GamesSessionError>(
+            GamesSessionResponse::CheckWordResult {
+                correct_positions: vec![0, 1, 3, 4],
+                contained_in_word: vec![],
+                keyboard: house_keyboard(),
+            },
+        ));
     assert!(!res.main_failed() && res.contains(&log));
 
     // Case 9: CheckWord success and has been guessed
     let res = gamessession_program.send(
         USER,
         GamesSessionAction::CheckWord {
+            game_id: 0,
             word: "human".to_string(),
         },
     );
-    let log = Log::builder()
-        .dest(USER)
-        .source(GAMES_SESSION_PROGRAM_ID)
-        .payload(GamesSessionResponse::GameOver(GameStatus::Win));
-    assert!(!res.main_failed() && res.contains(&log));
+    assert!(
+        !res.main_failed()
+            && contains_response(&res, |response| matches!(
+                response,
+                GamesSessionResponse::GameOver {
+                    status: GameStatus::Win,
+                    ..
+                }
+            ))
+    );
 
     // Case 10: CheckWord failed: The user is not in the game
     let res = gamessession_program.send(
         51,
         GamesSessionAction::CheckWord {
+            game_id: 0,
             word: "tests".to_string(),
         },
     );
-    assert!(res.main_failed());
+    assert!(contains_error(&res, GamesSessionError::NotInGame));
 
-    let state: GamesSessionState = gamessession_program.read_state(b"").unwrap();
+    let state: GamesSessionState = read_state(&gamessession_program);
     println!("{:?}", state);
+    let stats = &state.stats[0].1;
+    assert_eq!(
+        (
+            stats.games_played,
+            stats.current_streak,
+            stats.win_percentage()
+        ),
+        (1, 1, 100)
+    );
+    assert_eq!(stats.guess_distribution, vec![0, 1, 0, 0, 0]);
+
+    // Case 11: The finished game is archived with its transcript
+    let query = StateQuery::ArchiveByUser {
+        user: USER.into(),
+        page: 0,
+        page_size: 10,
+    };
+    let StateReply::Archive(archive) = gamessession_program.read_state(query).unwrap() else {
+        unreachable!()
+    };
+    assert_eq!(archive.len(), 1);
+    let archived_game = &archive[0].1;
+    assert!(matches!(archived_game.status, GameStatus::Win));
+    assert!(archived_game.word.is_some());
+    assert_eq!(archived_game.board.len(), 2);
+
+    let query = StateQuery::ArchiveByBlocks {
+        from: 0,
+        to: archived_game.ended_at,
+        page: 0,
+        page_size: 10,
+    };
+    let StateReply::Archive(archive) = gamessession_program.read_state(query).unwrap() else {
+        unreachable!()
+    };
+    assert_eq!(archive.len(), 1);
+    assert_eq!(archive[0].0, USER.into());
+
+    let query = StateQuery::ArchiveByBlocks {
+        from: archived_game.ended_at + 1,
+        to: u32::MAX,
+        page: 0,
+        page_size: 10,
+    };
+    let StateReply::Archive(archive) = gamessession_program.read_state(query).unwrap() else {
+        unreachable!()
+    };
+    assert!(archive.is_empty());
+
+    // Case 12: The win scores points in the current season
+    let leaderboard = |season_id| {
+        let query = StateQuery::Leaderboard {
+            season_id,
+            top: 10,
+            user: USER.into(),
+        };
+        match gamessession_program.read_state(query).unwrap() {
+            StateReply::Leaderboard {
+                season_id, rank, ..
+            } => (season_id, rank),
+            _ => unreachable!(),
+        }
+    };
+    let (season_id, rank) = leaderboard(None);
+    assert!(season_id == 0 && matches!(rank, Some((1, points)) if points > 100));
+
+    // Case 13: EndSeason failed: Only the owner can end a season
+    let res = gamessession_program.send(51, GamesSessionAction::EndSeason);
+    assert!(contains_error(&res, GamesSessionError::NotOwner));
+
+    // Case 14: EndSeason success, the rankings are frozen and a new season starts
+    let res = gamessession_program.send(USER, GamesSessionAction::EndSeason);
+    let log = Log::builder()
+        .dest(USER)
+        .source(GAMES_SESSION_PROGRAM_ID)
+        .payload(Ok::<_, GamesSessionError>(
+            GamesSessionResponse::SeasonEnded { season_id: 0 },
+        ));
+    assert!(!res.main_failed() && res.contains(&log));
+    assert_eq!(leaderboard(None), (1, None));
+    assert_eq!(leaderboard(Some(0)), (0, rank));
+    assert_eq!(leaderboard(Some(5)), (5, None));
+}
+
+#[test]
+fn test_absurdle() {
+    let system = System::new();
+    system.init_logger();
+
+    let gamessession_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/gamessession.opt.wasm")
+            .with_id(GAMES_SESSION_PROGRAM_ID)
+            .build(&system);
+    let wordle_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/wordle.opt.wasm")
+            .with_id(WORDLE_PROGRAM_ID)
+            .build(&system);
+
+    // Case 1: wordle_program and gamessession_program init
+    let res = wordle_program.send_bytes(USER, []);
+    assert!(!res.main_failed());
+    let res = wordle_program.send(
+        USER,
+        wordle_io::Action::AddTrustedProgram {
+            program_id: GAMES_SESSION_PROGRAM_ID.into(),
+        },
+    );
+    assert!(!res.main_failed());
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionInit {
+            wordle_program_id: WORDLE_PROGRAM_ID.into(),
+            ..Default::default()
+        },
+    );
+    assert!(!res.main_failed());
+
+    // Case 2: StartGame announces the commitment to the bank
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionAction::StartGame {
+            game_type: WORDLE_GAME_TYPE,
+            game_id: 0,
+            mode: GameMode::Absurdle,
+            hard_mode: false,
+            tries_limit: None,
+            timeout_blocks: None,
+        },
+    );
+    let started = res
+        .decoded_log::<Result<GamesSessionResponse, GamesSessionError>>()
+        .iter()
+        .find_map(|log| match log.payload() {
+            Ok(GamesSessionResponse::StartSuccess { commitment }) => Some(*commitment),
+            _ => None,
+        })
+        .expect("The game has not started");
+
+    // Case 3: "house" is dodged, then "horse" is the only word left
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionAction::CheckWord {
+            game_id: 0,
+            word: "house".to_string(),
+        },
+    );
+    assert!(!res.main_failed());
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionAction::CheckWord {
+            game_id: 0,
+            word: "horse".to_string(),
+        },
+    );
+
+    // Case 4: GameOver carries the bank and the commitment it is checked against
+    assert!(contains_response(&res, |response| matches!(
+        response,
+        GamesSessionResponse::GameOver {
+            status: GameStatus::Win,
+            salt,
+            bank,
+            commitment,
+            ..
+        } if *commitment == started && wordle_io::verify_bank_commitment(commitment, bank, salt)
+    )));
 }
 
 #[test]
@@ -131,55 +491,94 @@ fn test_tried_limit() {
     // Case 1: wordle_program init
     let res = wordle_program.send_bytes(USER, []);
     assert!(!res.main_failed());
+    let res = wordle_program.send(
+        USER,
+        wordle_io::Action::AddTrustedProgram {
+            program_id: GAMES_SESSION_PROGRAM_ID.into(),
+        },
+    );
+    assert!(!res.main_failed());
 
     // Case 2: gamessession_program init
     let res = gamessession_program.send(
         USER,
         GamesSessionInit {
             wordle_program_id: WORDLE_PROGRAM_ID.into(),
+            ..Default::default()
         },
     );
     assert!(!res.main_failed());
 
     // Case 3: StartGame success
-    let res = gamessession_program.send(USER, GamesSessionAction::StartGame);
-    let log = Log::builder()
-        .dest(USER)
-        .source(GAMES_SESSION_PROGRAM_ID)
-        .payload(GamesSessionResponse::StartSuccess);
-    assert!(!res.main_failed() && res.contains(&log));
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionAction::StartGame {
+            game_type: WORDLE_GAME_TYPE,
+            game_id: 0,
+            mode: GameMode::Classic,
+            hard_mode: false,
+            tries_limit: None,
+            timeout_blocks: None,
+        },
+    );
+    assert!(
+        !res.main_failed()
+            && contains_response(&res, |response| matches!(
+                response,
+                GamesSessionResponse::StartSuccess { .. }
+            ))
+    );
 
     for i in 0..5 {
         // Case 4: CheckWord success, but not guessed
         let res = gamessession_program.send(
             USER,
             GamesSessionAction::CheckWord {
+                game_id: 0,
                 word: "house".to_string(),
             },
         );
         if i == 4 {
-            let log = Log::builder()
-                .dest(USER)
-                .source(GAMES_SESSION_PROGRAM_ID)
-                .payload(GamesSessionResponse::GameOver(GameStatus::Lose));
-            assert!(!res.main_failed() && res.contains(&log));
+            assert!(
+                !res.main_failed()
+                    && contains_response(&res, |response| matches!(
+                        response,
+                        GamesSessionResponse::GameOver {
+                            status: GameStatus::Lose,
+                            tries: 5,
+                            board,
+                            ..
+                        } if board.len() == 5
+                    ))
+            );
         } else {
             let log = Log::builder()
                 .dest(USER)
                 .source(GAMES_SESSION_PROGRAM_ID)
-                .payload(GamesSessionResponse::CheckWordResult {
-                    correct_positions: vec![0, 1, 3, 4],
-                    contained_in_word: vec![],
-                });
+                .payload(Ok::<_, GamesSessionError>(
+                    GamesSessionResponse::CheckWordResult {
+                        correct_positions: vec![0, 1, 3, 4],
+                        contained_in_word: vec![],
+                        keyboard: house_keyboard(),
+                    },
+                ));
             assert!(!res.main_failed() && res.contains(&log));
         }
     }
-    let state: GamesSessionState = gamessession_program.read_state(b"").unwrap();
+    let state: GamesSessionState = read_state(&gamessession_program);
     println!("{:?}", state);
+    let stats = &state.stats[0].1;
+    assert_eq!(
+        (
+            stats.games_played,
+            stats.current_streak,
+            stats.win_percentage()
+        ),
+        (1, 0, 0)
+    );
 }
 
 #[test]
-#[ignore]
 fn test_dealyed_logic() {
     let system = System::new();
     system.init_logger();
@@ -196,32 +595,1466 @@ fn test_dealyed_logic() {
     // Case 1: wordle_program init
     let res = wordle_program.send_bytes(USER, []);
     assert!(!res.main_failed());
+    let res = wordle_program.send(
+        USER,
+        wordle_io::Action::AddTrustedProgram {
+            program_id: GAMES_SESSION_PROGRAM_ID.into(),
+        },
+    );
+    assert!(!res.main_failed());
 
     // Case 2: gamessession_program init
     let res = gamessession_program.send(
         USER,
         GamesSessionInit {
             wordle_program_id: WORDLE_PROGRAM_ID.into(),
+            ..Default::default()
         },
     );
     assert!(!res.main_failed());
 
     // Case 3: StartGame success
-    let res = gamessession_program.send(USER, GamesSessionAction::StartGame);
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionAction::StartGame {
+            game_type: WORDLE_GAME_TYPE,
+            game_id: 0,
+            mode: GameMode::Classic,
+            hard_mode: false,
+            tries_limit: None,
+            timeout_blocks: None,
+        },
+    );
+    assert!(
+        !res.main_failed()
+            && contains_response(&res, |response| matches!(
+                response,
+                GamesSessionResponse::StartSuccess { .. }
+            ))
+    );
+
+    // Case 4: Delayed equal to 200 blocks (10 minutes) for the delayed message, paid from its
+    // reservation
+    let results = system.spend_blocks(200);
+    assert!(results
+        .iter()
+        .any(|result| contains_response(result, |response| matches!(
+            response,
+            GamesSessionResponse::GameOver {
+                status: GameStatus::Lose,
+                ..
+            }
+        ))));
+    // The finished game is only kept in the archive
+    assert!(read_state(&gamessession_program).game_sessions.is_empty());
+}
+
+#[test]
+fn test_race() {
+    let system = System::new();
+    system.init_logger();
+
+    let gamessession_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/gamessession.opt.wasm")
+            .with_id(GAMES_SESSION_PROGRAM_ID)
+            .build(&system);
+    let wordle_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/wordle.opt.wasm")
+            .with_id(WORDLE_PROGRAM_ID)
+            .build(&system);
+
+    // Case 1: wordle_program init
+    let res = wordle_program.send_bytes(USER, []);
+    assert!(!res.main_failed());
+    let res = wordle_program.send(
+        USER,
+        wordle_io::Action::AddTrustedProgram {
+            program_id: GAMES_SESSION_PROGRAM_ID.into(),
+        },
+    );
+    assert!(!res.main_failed());
+
+    // Case 2: gamessession_program init
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionInit {
+            wordle_program_id: WORDLE_PROGRAM_ID.into(),
+            ..Default::default()
+        },
+    );
+    assert!(!res.main_failed());
+
+    // Case 3: CreateRoom success
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionAction::CreateRoom {
+            start_block: system.block_height() + 5,
+        },
+    );
     let log = Log::builder()
         .dest(USER)
         .source(GAMES_SESSION_PROGRAM_ID)
-        .payload(GamesSessionResponse::StartSuccess);
+        .payload(Ok::<_, GamesSessionError>(
+            GamesSessionResponse::RoomCreated { room_id: 0 },
+        ));
     assert!(!res.main_failed() && res.contains(&log));
-    
-    // Case 4: Delayed equal to 200 blocks (10 minutes) for the delayed message
-    let result = system.spend_blocks(200);
-    println!("{:?}", result);
+
+    // Case 4: JoinRoom success, the host is told about the new player
+    let res = gamessession_program.send(51, GamesSessionAction::JoinRoom { room_id: 0 });
     let log = Log::builder()
         .dest(USER)
         .source(GAMES_SESSION_PROGRAM_ID)
-        .payload(GamesSessionResponse::GameOver(GameStatus::Lose));
-    assert!(result[0].contains(&log));
-    let state: GamesSessionState = gamessession_program.read_state(b"").unwrap();
-    println!("{:?}", state);
-}
\ No newline at end of file
+        .payload(Ok::<_, GamesSessionError>(
+            GamesSessionResponse::RoomUpdate(RoomEvent::PlayerJoined {
+                room_id: 0,
+                player: 51.into(),
+            }),
+        ));
+    assert!(!res.main_failed() && res.contains(&log));
+
+    // Case 5: StartGame failed: The race has not started yet
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionAction::StartGame {
+            game_type: WORDLE_GAME_TYPE,
+            game_id: 0,
+            mode: GameMode::Race { room_id: 0 },
+            hard_mode: false,
+            tries_limit: None,
+            timeout_blocks: None,
+        },
+    );
+    assert!(contains_error(&res, GamesSessionError::RaceNotStarted));
+
+    system.spend_blocks(5);
+
+    // Case 6: JoinRoom failed: The race has already started
+    let res = gamessession_program.send(52, GamesSessionAction::JoinRoom { room_id: 0 });
+    assert!(contains_error(&res, GamesSessionError::RaceAlreadyStarted));
+
+    // Case 7: StartGame success for both players
+    for player in [USER, 51] {
+        let res = gamessession_program.send(
+            player,
+            GamesSessionAction::StartGame {
+                game_type: WORDLE_GAME_TYPE,
+                game_id: 0,
+                mode: GameMode::Race { room_id: 0 },
+                hard_mode: false,
+                tries_limit: None,
+                timeout_blocks: None,
+            },
+        );
+        assert!(!res.main_failed());
+    }
+
+    // Case 8: The host solves the shared word first and wins the race
+    for word in ["house", "human", "horse"] {
+        let res = gamessession_program.send(
+            USER,
+            GamesSessionAction::CheckWord {
+                game_id: 0,
+                word: word.to_string(),
+            },
+        );
+        assert!(!res.main_failed());
+        if contains_response(&res, |response| {
+            matches!(
+                response,
+                GamesSessionResponse::RoomUpdate(RoomEvent::RaceWon { .. })
+            )
+        }) {
+            break;
+        }
+    }
+    let state: GamesSessionState = read_state(&gamessession_program);
+    let room = &state.rooms[0].1;
+    assert_eq!(
+        room.winner.as_ref().map(|winner| winner.player),
+        Some(USER.into())
+    );
+}
+
+#[test]
+fn test_custom_limits() {
+    let system = System::new();
+    system.init_logger();
+
+    let gamessession_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/gamessession.opt.wasm")
+            .with_id(GAMES_SESSION_PROGRAM_ID)
+            .build(&system);
+    let wordle_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/wordle.opt.wasm")
+            .with_id(WORDLE_PROGRAM_ID)
+            .build(&system);
+
+    // Case 1: wordle_program init
+    let res = wordle_program.send_bytes(USER, []);
+    assert!(!res.main_failed());
+    let res = wordle_program.send(
+        USER,
+        wordle_io::Action::AddTrustedProgram {
+            program_id: GAMES_SESSION_PROGRAM_ID.into(),
+        },
+    );
+    assert!(!res.main_failed());
+
+    // Case 2: gamessession_program init failed: The default is out of its bounds
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionInit {
+            wordle_program_id: WORDLE_PROGRAM_ID.into(),
+            config: SessionConfig {
+                tries_limit: 7,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    );
+    assert!(res.main_failed());
+
+    // Case 3: gamessession_program init
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionInit {
+            wordle_program_id: WORDLE_PROGRAM_ID.into(),
+            ..Default::default()
+        },
+    );
+    assert!(!res.main_failed());
+
+    // Case 4: StartGame failed: The overrides are out of bounds
+    for (tries_limit, timeout_blocks, error) in [
+        (Some(7), None, GamesSessionError::TriesLimitOutOfBounds),
+        (None, Some(1), GamesSessionError::TimeoutOutOfBounds),
+    ] {
+        let res = gamessession_program.send(
+            USER,
+            GamesSessionAction::StartGame {
+                game_type: WORDLE_GAME_TYPE,
+                game_id: 0,
+                mode: GameMode::Classic,
+                hard_mode: false,
+                tries_limit,
+                timeout_blocks,
+            },
+        );
+        assert!(contains_error(&res, error));
+    }
+
+    // Case 5: StartGame success with two tries
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionAction::StartGame {
+            game_type: WORDLE_GAME_TYPE,
+            game_id: 0,
+            mode: GameMode::Classic,
+            hard_mode: false,
+            tries_limit: Some(2),
+            timeout_blocks: Some(50),
+        },
+    );
+    assert!(!res.main_failed());
+    let state: GamesSessionState = read_state(&gamessession_program);
+    let session_info = &state.game_sessions[0].1;
+    assert_eq!(
+        (session_info.tries_limit, session_info.timeout_blocks),
+        (2, 50)
+    );
+
+    // Case 6: The game is lost after the second wrong guess
+    let check_word = GamesSessionAction::CheckWord {
+        game_id: 0,
+        word: "house".to_string(),
+    };
+    let res = gamessession_program.send(USER, check_word.clone());
+    assert!(!res.main_failed());
+    let res = gamessession_program.send(USER, check_word);
+    assert!(contains_response(&res, |response| matches!(
+        response,
+        GamesSessionResponse::GameOver {
+            status: GameStatus::Lose,
+            tries: 2,
+            ..
+        }
+    )));
+    assert!(read_state(&gamessession_program).game_sessions.is_empty());
+}
+
+#[test]
+#[should_panic(expected = "Invalid tries limit")]
+fn test_tries_limit_cap() {
+    // Games past the tries the Wordle program allows could only end by timeout.
+    SessionConfig {
+        max_tries_limit: MAX_TRIES_LIMIT + 1,
+        ..Default::default()
+    }
+    .assert_valid();
+}
+
+#[test]
+fn test_reply_timeout() {
+    let system = System::new();
+    system.init_logger();
+
+    let gamessession_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/gamessession.opt.wasm")
+            .with_id(GAMES_SESSION_PROGRAM_ID)
+            .build(&system);
+
+    // Case 1: gamessession_program init, pointed at a user that never answers
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionInit {
+            wordle_program_id: 100.into(),
+            ..Default::default()
+        },
+    );
+    assert!(!res.main_failed());
+
+    // Case 2: StartGame waits for a reply
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionAction::StartGame {
+            game_type: WORDLE_GAME_TYPE,
+            game_id: 0,
+            mode: GameMode::Classic,
+            hard_mode: false,
+            tries_limit: None,
+            timeout_blocks: None,
+        },
+    );
+    assert!(!res.main_failed());
+
+    // Case 3: StartGame failed: the first request is still waiting for its reply
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionAction::StartGame {
+            game_type: WORDLE_GAME_TYPE,
+            game_id: 0,
+            mode: GameMode::Classic,
+            hard_mode: false,
+            tries_limit: None,
+            timeout_blocks: None,
+        },
+    );
+    assert!(contains_error(&res, GamesSessionError::WaitingForBackend));
+
+    // Case 4: The reply is waited for twice more, then the user is told and can retry
+    let config = SessionConfig::default();
+    let results =
+        system.spend_blocks(config.reply_timeout_blocks * (config.max_rewaits as u32 + 1));
+    assert!(results
+        .iter()
+        .any(|result| contains_response(result, |response| matches!(
+            response,
+            GamesSessionResponse::ReplyTimeout
+        ))));
+    let state: GamesSessionState = read_state(&gamessession_program);
+    assert!(state.game_sessions.is_empty());
+
+    // Case 5: The timeout check of a game still waiting to start waits for the request to end
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionAction::StartGame {
+            game_type: WORDLE_GAME_TYPE,
+            game_id: 0,
+            mode: GameMode::Classic,
+            hard_mode: false,
+            tries_limit: None,
+            timeout_blocks: Some(config.min_timeout_blocks),
+        },
+    );
+    assert!(!res.main_failed());
+    let results =
+        system.spend_blocks(config.reply_timeout_blocks * (config.max_rewaits as u32 + 2));
+    assert!(results.iter().all(|result| !result.main_failed()));
+    let state: GamesSessionState = read_state(&gamessession_program);
+    assert!(state.game_sessions.is_empty());
+}
+
+#[test]
+fn test_late_reply() {
+    const BACKEND: u64 = 100;
+
+    let system = System::new();
+    system.init_logger();
+
+    let gamessession_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/gamessession.opt.wasm")
+            .with_id(GAMES_SESSION_PROGRAM_ID)
+            .build(&system);
+
+    // Case 1: gamessession_program init, pointed at a user answering for the backend by hand
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionInit {
+            wordle_program_id: BACKEND.into(),
+            ..Default::default()
+        },
+    );
+    assert!(!res.main_failed());
+
+    // Case 2: StartGame is answered in time
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionAction::StartGame {
+            game_type: WORDLE_GAME_TYPE,
+            game_id: 0,
+            mode: GameMode::Classic,
+            hard_mode: false,
+            tries_limit: None,
+            timeout_blocks: None,
+        },
+    );
+    assert!(!res.main_failed());
+    let session_id = read_state(&gamessession_program).game_sessions[0]
+        .1
+        .session_id;
+    let request = Log::builder()
+        .source(GAMES_SESSION_PROGRAM_ID)
+        .dest(BACKEND);
+    let res = system.get_mailbox(BACKEND).reply(
+        request.clone(),
+        WordleEvent::GameStarted {
+            user: USER.into(),
+            session_id,
+            commitment: [0; 32],
+        },
+        0,
+    );
+    assert!(contains_response(&res, |response| matches!(
+        response,
+        GamesSessionResponse::StartSuccess { .. }
+    )));
+
+    // Case 3: CheckWord is not answered in time, the session waits for the late reply
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionAction::CheckWord {
+            game_id: 0,
+            word: "house".to_string(),
+        },
+    );
+    assert!(!res.main_failed());
+    let config = SessionConfig::default();
+    let results =
+        system.spend_blocks(config.reply_timeout_blocks * (config.max_rewaits as u32 + 1));
+    assert!(results
+        .iter()
+        .any(|result| contains_response(result, |response| matches!(
+            response,
+            GamesSessionResponse::ReplyTimeout
+        ))));
+    let state = read_state(&gamessession_program);
+    assert!(matches!(
+        state.game_sessions[0].1.session_status,
+        SessionStatus::WaitLateReply
+    ));
+
+    // Case 4: The next move waits for the backend to catch up
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionAction::CheckWord {
+            game_id: 0,
+            word: "horse".to_string(),
+        },
+    );
+    assert!(contains_error(&res, GamesSessionError::WaitingForBackend));
+
+    // Case 5: The late reply counts the try and tells the user its result
+    let res = system.get_mailbox(BACKEND).reply(
+        request,
+        WordleEvent::WordChecked {
+            user: USER.into(),
+            session_id,
+            correct_positions: vec![0],
+            contained_in_word: vec![1],
+        },
+        0,
+    );
+    assert!(contains_response(&res, |response| matches!(
+        response,
+        GamesSessionResponse::CheckWordResult { correct_positions, .. } if *correct_positions == [0]
+    )));
+    let session_info = &read_state(&gamessession_program).game_sessions[0].1;
+    assert_eq!(session_info.tries, 1);
+    assert!(matches!(
+        session_info.session_status,
+        SessionStatus::WaitUserInput
+    ));
+}
+
+#[test]
+fn test_wordle_failure() {
+    let system = System::new();
+    system.init_logger();
+
+    let gamessession_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/gamessession.opt.wasm")
+            .with_id(GAMES_SESSION_PROGRAM_ID)
+            .build(&system);
+    let wordle_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/wordle.opt.wasm")
+            .with_id(WORDLE_PROGRAM_ID)
+            .build(&system);
+
+    // Case 1: wordle_program init without trusting gamessession_program
+    let res = wordle_program.send_bytes(USER, []);
+    assert!(!res.main_failed());
+
+    // Case 2: gamessession_program init
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionInit {
+            wordle_program_id: WORDLE_PROGRAM_ID.into(),
+            ..Default::default()
+        },
+    );
+    assert!(!res.main_failed());
+
+    // Case 3: StartGame failed in the Wordle program, the user can start again
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionAction::StartGame {
+            game_type: WORDLE_GAME_TYPE,
+            game_id: 0,
+            mode: GameMode::Classic,
+            hard_mode: false,
+            tries_limit: None,
+            timeout_blocks: None,
+        },
+    );
+    let log = Log::builder()
+        .dest(USER)
+        .source(GAMES_SESSION_PROGRAM_ID)
+        .payload(Ok::<_, GamesSessionError>(
+            GamesSessionResponse::BackendFailed(BackendError::NotTrusted),
+        ));
+    assert!(!res.main_failed() && res.contains(&log));
+    let state: GamesSessionState = read_state(&gamessession_program);
+    assert!(state.game_sessions.is_empty());
+}
+
+#[test]
+fn test_give_up() {
+    let system = System::new();
+    system.init_logger();
+
+    let gamessession_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/gamessession.opt.wasm")
+            .with_id(GAMES_SESSION_PROGRAM_ID)
+            .build(&system);
+    let wordle_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/wordle.opt.wasm")
+            .with_id(WORDLE_PROGRAM_ID)
+            .build(&system);
+
+    // Case 1: wordle_program init
+    let res = wordle_program.send_bytes(USER, []);
+    assert!(!res.main_failed());
+    let res = wordle_program.send(
+        USER,
+        wordle_io::Action::AddTrustedProgram {
+            program_id: GAMES_SESSION_PROGRAM_ID.into(),
+        },
+    );
+    assert!(!res.main_failed());
+
+    // Case 2: gamessession_program init
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionInit {
+            wordle_program_id: WORDLE_PROGRAM_ID.into(),
+            ..Default::default()
+        },
+    );
+    assert!(!res.main_failed());
+
+    // Case 3: GiveUp failed: The user is not in the game
+    let res = gamessession_program.send(USER, GamesSessionAction::GiveUp { game_id: 0 });
+    assert!(contains_error(&res, GamesSessionError::NotInGame));
+
+    let start_game = GamesSessionAction::StartGame {
+        game_type: WORDLE_GAME_TYPE,
+        game_id: 0,
+        mode: GameMode::Classic,
+        hard_mode: false,
+        tries_limit: None,
+        timeout_blocks: None,
+    };
+
+    // Case 4: StartGame success
+    let res = gamessession_program.send(USER, start_game.clone());
+    assert!(!res.main_failed());
+
+    // Case 5: GiveUp success, the game is lost and its word revealed
+    let res = gamessession_program.send(USER, GamesSessionAction::GiveUp { game_id: 0 });
+    assert!(
+        !res.main_failed()
+            && contains_response(&res, |response| matches!(
+                response,
+                GamesSessionResponse::GameOver {
+                    status: GameStatus::Lose,
+                    tries: 0,
+                    ..
+                }
+            ))
+    );
+
+    // Case 6: A new game can be started right away
+    let res = gamessession_program.send(USER, start_game);
+    assert!(
+        !res.main_failed()
+            && contains_response(&res, |response| matches!(
+                response,
+                GamesSessionResponse::StartSuccess { .. }
+            ))
+    );
+}
+
+#[test]
+fn test_entry_fee() {
+    const FEE: u128 = 10_000_000_000_000;
+
+    let system = System::new();
+    system.init_logger();
+    system.mint_to(USER, 10 * FEE);
+
+    let gamessession_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/gamessession.opt.wasm")
+            .with_id(GAMES_SESSION_PROGRAM_ID)
+            .build(&system);
+    let wordle_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/wordle.opt.wasm")
+            .with_id(WORDLE_PROGRAM_ID)
+            .build(&system);
+
+    // Case 1: wordle_program init
+    let res = wordle_program.send_bytes(USER, []);
+    assert!(!res.main_failed());
+    let res = wordle_program.send(
+        USER,
+        wordle_io::Action::AddTrustedProgram {
+            program_id: GAMES_SESSION_PROGRAM_ID.into(),
+        },
+    );
+    assert!(!res.main_failed());
+
+    // Case 2: gamessession_program init with a 10% rake
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionInit {
+            wordle_program_id: WORDLE_PROGRAM_ID.into(),
+            entry_fee: FEE,
+            rake_percent: 10,
+            ..Default::default()
+        },
+    );
+    assert!(!res.main_failed());
+
+    let start_game = GamesSessionAction::StartGame {
+        game_type: WORDLE_GAME_TYPE,
+        game_id: 0,
+        mode: GameMode::Classic,
+        hard_mode: false,
+        tries_limit: None,
+        timeout_blocks: None,
+    };
+
+    // Case 3: StartGame failed: The value must equal the entry fee
+    let res = gamessession_program.send(USER, start_game.clone());
+    assert!(contains_error(&res, GamesSessionError::WrongEntryFee));
+    // and leaves no session behind.
+    assert!(read_state(&gamessession_program).game_sessions.is_empty());
+
+    // Case 4: StartGame success, the fee is split between the pool and the rake
+    let res = gamessession_program.send_with_value(USER, start_game.clone(), FEE);
+    assert!(!res.main_failed());
+    assert_eq!(system.balance_of(USER), 9 * FEE);
+    let state = read_state(&gamessession_program);
+    assert_eq!(
+        (state.prize_pool, state.rake_balance),
+        (9 * FEE / 10, FEE / 10)
+    );
+
+    // Case 5: Giving up pays nothing and leaves the fee in the pool
+    let res = gamessession_program.send(USER, GamesSessionAction::GiveUp { game_id: 0 });
+    assert!(!res.main_failed());
+    assert_eq!(read_state(&gamessession_program).prize_pool, 9 * FEE / 10);
+
+    // Case 6: A win pays out on the curve, capped by the pool
+    let res = gamessession_program.send_with_value(USER, start_game, FEE);
+    assert!(!res.main_failed());
+    for word in ["house", "human", "horse"] {
+        let res = gamessession_program.send(
+            USER,
+            GamesSessionAction::CheckWord {
+                game_id: 0,
+                word: word.to_string(),
+            },
+        );
+        assert!(!res.main_failed());
+        if contains_response(&res, |response| {
+            matches!(response, GamesSessionResponse::PrizePaid { .. })
+        }) {
+            break;
+        }
+    }
+    let state = read_state(&gamessession_program);
+    assert_eq!((state.prize_pool, state.rake_balance), (0, 2 * FEE / 10));
+    system.claim_value_from_mailbox(USER);
+    assert_eq!(system.balance_of(USER), 8 * FEE + 18 * FEE / 10);
+
+    // Case 7: Withdraw failed: Only the owner can withdraw
+    let res = gamessession_program.send(51, GamesSessionAction::Withdraw { amount: FEE / 10 });
+    assert!(contains_error(&res, GamesSessionError::NotOwner));
+
+    // Case 8: Withdraw failed: Not enough rake to withdraw
+    let res = gamessession_program.send(USER, GamesSessionAction::Withdraw { amount: FEE });
+    assert!(contains_error(&res, GamesSessionError::NotEnoughRake));
+
+    // Case 9: Withdraw success
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionAction::Withdraw {
+            amount: 2 * FEE / 10,
+        },
+    );
+    assert!(!res.main_failed());
+    system.claim_value_from_mailbox(USER);
+    assert_eq!(system.balance_of(USER), 10 * FEE);
+
+    // Case 10: Hint failed: A hint costs the entry fee spread over the tries
+    let res = gamessession_program.send_with_value(
+        USER,
+        GamesSessionAction::StartGame {
+            game_type: WORDLE_GAME_TYPE,
+            game_id: 0,
+            mode: GameMode::Classic,
+            hard_mode: false,
+            tries_limit: Some(5),
+            timeout_blocks: None,
+        },
+        FEE,
+    );
+    assert!(!res.main_failed());
+    let res = gamessession_program.send(USER, GamesSessionAction::Hint { game_id: 0 });
+    assert!(contains_error(&res, GamesSessionError::WrongEntryFee));
+
+    // Case 11: Hint success, the fee is shared like an entry fee and no try is used
+    let res = gamessession_program.send_with_value(
+        USER,
+        GamesSessionAction::Hint { game_id: 0 },
+        FEE / 5,
+    );
+    assert!(contains_response(&res, |response| {
+        matches!(response, GamesSessionResponse::HintRevealed { .. })
+    }));
+    let state = read_state(&gamessession_program);
+    assert_eq!(
+        (state.prize_pool, state.rake_balance),
+        (9 * FEE / 10 + 9 * FEE / 50, FEE / 10 + FEE / 50)
+    );
+    let session_info = &state.game_sessions[0].1;
+    assert_eq!((session_info.tries_used(), session_info.hints), (0, 1));
+}
+
+#[test]
+fn test_hint_price_rounded_down() {
+    // A fee too small to spread over the tries makes hints cost a try, as in a free game.
+    let session_info = SessionInfo {
+        entry_fee: 5,
+        tries_limit: 6,
+        tries: 2,
+        hints: 1,
+        ..Default::default()
+    };
+    assert_eq!(session_info.hint_price(), 0);
+    assert_eq!(session_info.tries_used(), 3);
+}
+
+#[test]
+fn test_concurrent_games() {
+    let system = System::new();
+    system.init_logger();
+
+    let gamessession_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/gamessession.opt.wasm")
+            .with_id(GAMES_SESSION_PROGRAM_ID)
+            .build(&system);
+    let wordle_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/wordle.opt.wasm")
+            .with_id(WORDLE_PROGRAM_ID)
+            .build(&system);
+
+    // Case 1: wordle_program init
+    let res = wordle_program.send_bytes(USER, []);
+    assert!(!res.main_failed());
+    let res = wordle_program.send(
+        USER,
+        wordle_io::Action::AddTrustedProgram {
+            program_id: GAMES_SESSION_PROGRAM_ID.into(),
+        },
+    );
+    assert!(!res.main_failed());
+
+    // Case 2: gamessession_program init
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionInit {
+            wordle_program_id: WORDLE_PROGRAM_ID.into(),
+            ..Default::default()
+        },
+    );
+    assert!(!res.main_failed());
+
+    // Case 3: StartGame success for a daily and a practice game
+    for (game_id, mode) in [(0, GameMode::Daily), (1, GameMode::Classic)] {
+        let res = gamessession_program.send(
+            USER,
+            GamesSessionAction::StartGame {
+                game_type: WORDLE_GAME_TYPE,
+                game_id,
+                mode,
+                hard_mode: false,
+                tries_limit: None,
+                timeout_blocks: None,
+            },
+        );
+        assert!(!res.main_failed());
+    }
+
+    // Case 4: StartGame failed: The game is already in progress
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionAction::StartGame {
+            game_type: WORDLE_GAME_TYPE,
+            game_id: 1,
+            mode: GameMode::Classic,
+            hard_mode: false,
+            tries_limit: None,
+            timeout_blocks: None,
+        },
+    );
+    assert!(contains_error(&res, GamesSessionError::GameInProgress));
+
+    // Case 5: Giving up the practice game leaves the daily game open
+    let res = gamessession_program.send(USER, GamesSessionAction::GiveUp { game_id: 1 });
+    assert!(
+        !res.main_failed()
+            && contains_response(&res, |response| matches!(
+                response,
+                GamesSessionResponse::GameOver { game_id: 1, .. }
+            ))
+    );
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionAction::CheckWord {
+            game_id: 0,
+            word: "house".to_string(),
+        },
+    );
+    assert!(!res.main_failed());
+
+    // Case 6: CheckWord failed: There is no game with this id
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionAction::CheckWord {
+            game_id: 2,
+            word: "house".to_string(),
+        },
+    );
+    assert!(contains_error(&res, GamesSessionError::NotInGame));
+}
+
+#[test]
+fn test_pebbles() {
+    let system = System::new();
+    system.init_logger();
+
+    let gamessession_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/gamessession.opt.wasm")
+            .with_id(GAMES_SESSION_PROGRAM_ID)
+            .build(&system);
+    let pebbles_program =
+        Program::mock_with_id(&system, PEBBLES_PROGRAM_ID, PebblesBackend::default());
+
+    // Case 1: pebbles_program init, the program always takes one pebble
+    let res = pebbles_program.send_bytes(USER, []);
+    assert!(!res.main_failed());
+
+    // Case 2: gamessession_program init
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionInit {
+            wordle_program_id: WORDLE_PROGRAM_ID.into(),
+            ..Default::default()
+        },
+    );
+    assert!(!res.main_failed());
+
+    let register_backend = GamesSessionAction::RegisterBackend {
+        game_type: PEBBLES_GAME_TYPE,
+        program_id: PEBBLES_PROGRAM_ID.into(),
+        codec: Codec::Pebbles {
+            difficulty: DifficultyLevel::Hard,
+            pebbles_count: 9,
+            max_pebbles_per_turn: 3,
+        },
+    };
+
+    // Case 3: RegisterBackend failed: Only the owner can register a backend
+    let res = gamessession_program.send(51, register_backend.clone());
+    assert!(contains_error(&res, GamesSessionError::NotOwner));
+
+    // Case 4: RegisterBackend success
+    let res = gamessession_program.send(USER, register_backend.clone());
+    assert!(
+        !res.main_failed()
+            && contains_response(&res, |response| matches!(
+                response,
+                GamesSessionResponse::BackendRegistered {
+                    game_type: PEBBLES_GAME_TYPE
+                }
+            ))
+    );
+
+    // Case 5: RegisterBackend failed: The game type is already registered
+    let res = gamessession_program.send(USER, register_backend);
+    assert!(contains_error(
+        &res,
+        GamesSessionError::GameTypeAlreadyRegistered
+    ));
+
+    let start_game = GamesSessionAction::StartGame {
+        game_type: PEBBLES_GAME_TYPE,
+        game_id: 0,
+        mode: GameMode::Classic,
+        hard_mode: false,
+        tries_limit: None,
+        timeout_blocks: None,
+    };
+
+    // Case 6: StartGame success
+    let res = gamessession_program.send(USER, start_game.clone());
+    assert!(
+        !res.main_failed()
+            && contains_response(&res, |response| matches!(
+                response,
+                GamesSessionResponse::PebblesStarted
+            ))
+    );
+
+    // Case 7: StartGame failed: The game backend is busy
+    let res = gamessession_program.send(51, start_game);
+    assert!(contains_error(&res, GamesSessionError::BackendBusy));
+
+    // Case 8: CheckWord failed: The game does not take words
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionAction::CheckWord {
+            game_id: 0,
+            word: "house".to_string(),
+        },
+    );
+    assert!(contains_error(&res, GamesSessionError::WrongGameType));
+
+    // Case 9: TakePebbles failed: the pebble program rejects the move
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionAction::TakePebbles {
+            game_id: 0,
+            pebbles: 5,
+        },
+    );
+    assert!(
+        !res.main_failed()
+            && contains_response(&res, |response| matches!(
+                response,
+                GamesSessionResponse::BackendFailed(BackendError::InvalidMove)
+            ))
+    );
+
+    // Case 10: TakePebbles until the game is over, which frees the backend
+    let mut game_over = false;
+    for _ in 0..5 {
+        let res = gamessession_program.send(
+            USER,
+            GamesSessionAction::TakePebbles {
+                game_id: 0,
+                pebbles: 1,
+            },
+        );
+        assert!(!res.main_failed());
+        if contains_response(&res, |response| {
+            matches!(
+                response,
+                GamesSessionResponse::PebblesOver { game_id: 0, .. }
+            )
+        }) {
+            game_over = true;
+            break;
+        }
+    }
+    assert!(game_over);
+    let state = read_state(&gamessession_program);
+    assert!(state
+        .backends
+        .iter()
+        .all(|(_, backend)| backend.occupied_by.is_none()));
+
+    // Case 11: The game is archived under its game type, but does not count in the Wordle stats
+    assert!(state.stats.is_empty());
+    let query = StateQuery::ArchiveByUser {
+        user: USER.into(),
+        page: 0,
+        page_size: 10,
+    };
+    let StateReply::Archive(archive) = gamessession_program.read_state(query).unwrap() else {
+        unreachable!()
+    };
+    assert_eq!(archive[0].1.game_type, PEBBLES_GAME_TYPE);
+}
+
+#[test]
+fn test_backend_upgrade() {
+    let system = System::new();
+    system.init_logger();
+
+    let gamessession_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/gamessession.opt.wasm")
+            .with_id(GAMES_SESSION_PROGRAM_ID)
+            .build(&system);
+    let wordle_programs = [WORDLE_PROGRAM_ID, NEW_WORDLE_PROGRAM_ID].map(|program_id| {
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/wordle.opt.wasm")
+            .with_id(program_id)
+            .build(&system)
+    });
+
+    // Case 1: wordle_program init for the old and the new program
+    for wordle_program in &wordle_programs {
+        let res = wordle_program.send_bytes(USER, []);
+        assert!(!res.main_failed());
+        let res = wordle_program.send(
+            USER,
+            wordle_io::Action::AddTrustedProgram {
+                program_id: GAMES_SESSION_PROGRAM_ID.into(),
+            },
+        );
+        assert!(!res.main_failed());
+    }
+
+    // Case 2: gamessession_program init
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionInit {
+            wordle_program_id: WORDLE_PROGRAM_ID.into(),
+            ..Default::default()
+        },
+    );
+    assert!(!res.main_failed());
+
+    let start_game = |game_id| GamesSessionAction::StartGame {
+        game_type: WORDLE_GAME_TYPE,
+        game_id,
+        mode: GameMode::Classic,
+        hard_mode: false,
+        tries_limit: None,
+        timeout_blocks: None,
+    };
+    // Not in the bank of words, so it never ends the game.
+    let check_word = |game_id| GamesSessionAction::CheckWord {
+        game_id,
+        word: "plumb".to_string(),
+    };
+    let upgrade_backend = GamesSessionAction::UpgradeBackend {
+        game_type: WORDLE_GAME_TYPE,
+        program_id: NEW_WORDLE_PROGRAM_ID.into(),
+    };
+
+    // Case 3: StartGame success on the old program
+    let res = gamessession_program.send(USER, start_game(0));
+    assert!(!res.main_failed());
+
+    // Case 4: UpgradeBackend failed: Only the owner can upgrade a backend
+    let res = gamessession_program.send(51, upgrade_backend.clone());
+    assert!(contains_error(&res, GamesSessionError::NotOwner));
+
+    // Case 5: UpgradeBackend success, the old program is kept for the game in progress
+    let res = gamessession_program.send(USER, upgrade_backend);
+    assert!(!res.main_failed());
+    let state = read_state(&gamessession_program);
+    let (_, backend) = &state.backends[0];
+    assert_eq!(backend.program_id, NEW_WORDLE_PROGRAM_ID.into());
+    assert_eq!(backend.retired_program_ids, vec![WORDLE_PROGRAM_ID.into()]);
+
+    // Case 6: New games start on the new program, the old game goes on on the old one
+    let res = gamessession_program.send(USER, start_game(1));
+    assert!(!res.main_failed());
+    let state = read_state(&gamessession_program);
+    let program_ids: Vec<ActorId> = state
+        .game_sessions
+        .iter()
+        .map(|(_, session_info)| session_info.program_id)
+        .collect();
+    assert!(
+        program_ids.contains(&WORDLE_PROGRAM_ID.into())
+            && program_ids.contains(&NEW_WORDLE_PROGRAM_ID.into())
+    );
+    let res = gamessession_program.send(USER, check_word(0));
+    assert!(
+        !res.main_failed()
+            && contains_response(&res, |response| matches!(
+                response,
+                GamesSessionResponse::CheckWordResult { .. }
+            ))
+    );
+
+    // Case 7: EndSession failed: The game is on the current backend
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionAction::EndSession {
+            user: USER.into(),
+            game_id: 1,
+        },
+    );
+    assert!(contains_error(&res, GamesSessionError::NotOnRetiredProgram));
+
+    // Case 8: EndSession success, the old program is dropped once its last game has ended
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionAction::EndSession {
+            user: USER.into(),
+            game_id: 0,
+        },
+    );
+    assert!(
+        !res.main_failed()
+            && contains_response(&res, |response| matches!(
+                response,
+                GamesSessionResponse::SessionEnded { game_id: 0 }
+            ))
+    );
+    let state = read_state(&gamessession_program);
+    assert!(state.backends[0].1.retired_program_ids.is_empty());
+
+    // Case 9: CheckWord failed: The user is not in the game
+    let res = gamessession_program.send(USER, check_word(0));
+    assert!(contains_error(&res, GamesSessionError::NotInGame));
+}
+
+#[test]
+fn test_session_keys() {
+    let system = System::new();
+    system.init_logger();
+
+    let gamessession_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/gamessession.opt.wasm")
+            .with_id(GAMES_SESSION_PROGRAM_ID)
+            .build(&system);
+    let wordle_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/wordle.opt.wasm")
+            .with_id(WORDLE_PROGRAM_ID)
+            .build(&system);
+
+    // Case 1: wordle_program init
+    let res = wordle_program.send_bytes(USER, []);
+    assert!(!res.main_failed());
+    let res = wordle_program.send(
+        USER,
+        wordle_io::Action::AddTrustedProgram {
+            program_id: GAMES_SESSION_PROGRAM_ID.into(),
+        },
+    );
+    assert!(!res.main_failed());
+
+    // Case 2: gamessession_program init
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionInit {
+            wordle_program_id: WORDLE_PROGRAM_ID.into(),
+            ..Default::default()
+        },
+    );
+    assert!(!res.main_failed());
+
+    let create_session_key = |duration_blocks| GamesSessionAction::CreateSessionKey {
+        key: SESSION_KEY.into(),
+        duration_blocks,
+        actions: vec![ActionKind::StartGame, ActionKind::CheckWord],
+    };
+    // Not in the bank of words, so it never ends the game.
+    let check_word = GamesSessionAction::CheckWord {
+        game_id: 0,
+        word: "plumb".to_string(),
+    };
+
+    // Case 3: CreateSessionKey failed: the duration is out of bounds
+    let res = gamessession_program.send(USER, create_session_key(0));
+    assert!(contains_error(
+        &res,
+        GamesSessionError::SessionKeyDurationOutOfBounds
+    ));
+
+    let accept_session_key = GamesSessionAction::AcceptSessionKey { owner: USER.into() };
+
+    // Case 4: CreateSessionKey success
+    let res = gamessession_program.send(USER, create_session_key(100));
+    assert!(
+        !res.main_failed()
+            && contains_response(&res, |response| matches!(
+                response,
+                GamesSessionResponse::SessionKeyCreated { .. }
+            ))
+    );
+
+    // Case 5: CreateSessionKey failed: the key is offered to the user already
+    let res = gamessession_program.send(51, create_session_key(100));
+    assert!(contains_error(&res, GamesSessionError::InvalidSessionKey));
+
+    // Case 6: Until the key accepts, its moves are its own
+    let res = gamessession_program.send(SESSION_KEY, check_word.clone());
+    assert!(contains_error(&res, GamesSessionError::NotInGame));
+    let res = gamessession_program.send(51, accept_session_key.clone());
+    assert!(contains_error(&res, GamesSessionError::SessionKeyNotFound));
+    let res = gamessession_program.send(SESSION_KEY, accept_session_key.clone());
+    assert!(
+        !res.main_failed()
+            && contains_response(&res, |response| matches!(
+                response,
+                GamesSessionResponse::SessionKeyAccepted { .. }
+            ))
+    );
+
+    // Case 7: StartGame and CheckWord sent by the key are moves of the user
+    let res = gamessession_program.send(
+        SESSION_KEY,
+        GamesSessionAction::StartGame {
+            game_type: WORDLE_GAME_TYPE,
+            game_id: 0,
+            mode: GameMode::Classic,
+            hard_mode: false,
+            tries_limit: None,
+            timeout_blocks: None,
+        },
+    );
+    assert!(!res.main_failed());
+    let res = gamessession_program.send(SESSION_KEY, check_word.clone());
+    assert!(
+        !res.main_failed()
+            && contains_response(&res, |response| matches!(
+                response,
+                GamesSessionResponse::CheckWordResult { .. }
+            ))
+    );
+    let state = read_state(&gamessession_program);
+    assert_eq!(state.game_sessions[0].0, (USER.into(), 0));
+    assert_eq!(state.game_sessions[0].1.tries, 1);
+
+    // Case 8: GiveUp failed: the key is not allowed to give up
+    let res = gamessession_program.send(SESSION_KEY, GamesSessionAction::GiveUp { game_id: 0 });
+    assert!(contains_error(&res, GamesSessionError::ActionNotAllowed));
+
+    // Case 9: RevokeSessionKey failed: only the user or the key can revoke the key
+    let res = gamessession_program.send(
+        51,
+        GamesSessionAction::RevokeSessionKey {
+            key: SESSION_KEY.into(),
+        },
+    );
+    assert!(contains_error(&res, GamesSessionError::SessionKeyNotFound));
+
+    // Case 10: The key revokes itself, its moves are its own again
+    let res = gamessession_program.send(
+        SESSION_KEY,
+        GamesSessionAction::RevokeSessionKey {
+            key: SESSION_KEY.into(),
+        },
+    );
+    assert!(!res.main_failed());
+    let res = gamessession_program.send(SESSION_KEY, check_word.clone());
+    assert!(contains_error(&res, GamesSessionError::NotInGame));
+
+    // Case 11: A new key expires after its duration and is then dropped
+    let res = gamessession_program.send(USER, create_session_key(20));
+    assert!(!res.main_failed());
+    let res = gamessession_program.send(SESSION_KEY, accept_session_key);
+    assert!(!res.main_failed());
+    system.spend_blocks(20);
+    let res = gamessession_program.send(SESSION_KEY, check_word.clone());
+    assert!(contains_error(&res, GamesSessionError::SessionKeyExpired));
+    let res = gamessession_program.send(SESSION_KEY, check_word);
+    assert!(contains_error(&res, GamesSessionError::NotInGame));
+    assert!(read_state(&gamessession_program).session_keys.is_empty());
+}
+
+#[test]
+fn test_hints() {
+    let system = System::new();
+    system.init_logger();
+
+    let gamessession_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/gamessession.opt.wasm")
+            .with_id(GAMES_SESSION_PROGRAM_ID)
+            .build(&system);
+    let wordle_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/wordle.opt.wasm")
+            .with_id(WORDLE_PROGRAM_ID)
+            .build(&system);
+
+    // Case 1: wordle_program init
+    let res = wordle_program.send_bytes(USER, []);
+    assert!(!res.main_failed());
+    let res = wordle_program.send(
+        USER,
+        wordle_io::Action::AddTrustedProgram {
+            program_id: GAMES_SESSION_PROGRAM_ID.into(),
+        },
+    );
+    assert!(!res.main_failed());
+
+    // Case 2: gamessession_program init
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionInit {
+            wordle_program_id: WORDLE_PROGRAM_ID.into(),
+            ..Default::default()
+        },
+    );
+    assert!(!res.main_failed());
+
+    // Case 3: Hint failed: The user is not in the game
+    let res = gamessession_program.send(USER, GamesSessionAction::Hint { game_id: 0 });
+    assert!(contains_error(&res, GamesSessionError::NotInGame));
+
+    // Case 4: StartGame success with three tries
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionAction::StartGame {
+            game_type: WORDLE_GAME_TYPE,
+            game_id: 0,
+            mode: GameMode::Classic,
+            hard_mode: false,
+            tries_limit: Some(3),
+            timeout_blocks: None,
+        },
+    );
+    assert!(!res.main_failed());
+
+    // Case 5: Two hints reveal two letters, marked as correct on the keyboard
+    let mut positions = Vec::new();
+    for _ in 0..2 {
+        let res = gamessession_program.send(USER, GamesSessionAction::Hint { game_id: 0 });
+        let position = res
+            .decoded_log::<Result<GamesSessionResponse, GamesSessionError>>()
+            .iter()
+            .find_map(|log| match log.payload() {
+                Ok(GamesSessionResponse::HintRevealed {
+                    position,
+                    letter,
+                    keyboard,
+                }) if keyboard[(letter - b'a') as usize] == LetterStatus::Correct => {
+                    Some(*position)
+                }
+                _ => None,
+            })
+            .expect("No letter was revealed");
+        positions.push(position);
+    }
+    assert_ne!(positions[0], positions[1]);
+
+    // Case 6: Hint failed: Each hint used a try and the last one is kept for a guess
+    let res = gamessession_program.send(USER, GamesSessionAction::Hint { game_id: 0 });
+    assert!(contains_error(&res, GamesSessionError::HintNotAvailable));
+
+    // Case 7: The wrong guess uses up the last try, the game is lost
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionAction::CheckWord {
+            game_id: 0,
+            word: "plumb".to_string(),
+        },
+    );
+    assert!(contains_response(&res, |response| {
+        matches!(
+            response,
+            GamesSessionResponse::GameOver {
+                status: GameStatus::Lose,
+                tries: 1,
+                ..
+            }
+        )
+    }));
+
+    // Case 8: The hints are archived with the game
+    let res: StateReply = gamessession_program
+        .read_state(StateQuery::ArchiveByUser {
+            user: USER.into(),
+            page: 0,
+            page_size: 10,
+        })
+        .unwrap();
+    let StateReply::Archive(archive) = res else {
+        unreachable!()
+    };
+    assert_eq!((archive[0].1.tries, archive[0].1.hints), (1, 2));
+}
+
+#[test]
+fn test_unexpected_reply() {
+    const FEE: u128 = 10_000_000_000_000;
+
+    let system = System::new();
+    system.init_logger();
+    system.mint_to(USER, 10 * FEE);
+
+    let gamessession_program =
+        ProgramBuilder::from_file("../target/wasm32-unknown-unknown/debug/gamessession.opt.wasm")
+            .with_id(GAMES_SESSION_PROGRAM_ID)
+            .build(&system);
+    let faulty_program = Program::mock_with_id(&system, WORDLE_PROGRAM_ID, FaultyBackend);
+
+    // Case 1: faulty_program init
+    let res = faulty_program.send_bytes(USER, []);
+    assert!(!res.main_failed());
+
+    // Case 2: gamessession_program init with a 10% rake
+    let res = gamessession_program.send(
+        USER,
+        GamesSessionInit {
+            wordle_program_id: WORDLE_PROGRAM_ID.into(),
+            entry_fee: FEE,
+            rake_percent: 10,
+            ..Default::default()
+        },
+    );
+    assert!(!res.main_failed());
+
+    // Case 3: StartGame gets a revealed word instead of the start of the game, which fails the
+    // request and refunds the fee
+    let res = gamessession_program.send_with_value(
+        USER,
+        GamesSessionAction::StartGame {
+            game_type: WORDLE_GAME_TYPE,
+            game_id: 0,
+            mode: GameMode::Classic,
+            hard_mode: false,
+            tries_limit: None,
+            timeout_blocks: None,
+        },
+        FEE,
+    );
+    assert!(contains_response(&res, |response| matches!(
+        response,
+        GamesSessionResponse::BackendFailed(BackendError::Unknown)
+    )));
+    system.claim_value_from_mailbox(USER);
+    assert_eq!(system.balance_of(USER), 10 * FEE);
+
+    // Case 4: Nothing is taken and the game can be started again
+    let state = read_state(&gamessession_program);
+    assert!(state.game_sessions.is_empty());
+    assert_eq!((state.prize_pool, state.rake_balance), (0, 0));
+}
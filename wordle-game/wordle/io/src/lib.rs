@@ -1,7 +1,8 @@
 #![no_std]
 
-use gmeta::{InOut, Metadata};
-use gstd::{prelude::*, ActorId};
+use blake2::{digest::consts::U32, Blake2b, Digest};
+use gmeta::{InOut, Metadata, Out};
+use gstd::{prelude::*, ActorId, MessageId};
 
 pub struct WordleMetadata;
 
@@ -11,23 +12,177 @@ impl Metadata for WordleMetadata {
     type Others = ();
     type Reply = ();
     type Signal = ();
-    type State = ();
+    type State = Out<WordleState>;
 }
 
 #[derive(Debug, Clone, Encode, Decode, TypeInfo)]
 pub enum Action {
-    StartGame { user: ActorId },
-    CheckWord { user: ActorId, word: String },
+    // A game is keyed by the id of the session that started it, so a game id reused by the
+    // session never reaches an older game. A user can have several games open at once.
+    StartGame {
+        user: ActorId,
+        session_id: MessageId,
+        mode: GameMode,
+        hard_mode: bool,
+    },
+    CheckWord {
+        user: ActorId,
+        session_id: MessageId,
+        word: String,
+    },
+    // Reveals the word and the board of a finished game and closes it.
+    RevealWord {
+        user: ActorId,
+        session_id: MessageId,
+    },
+    // Only the owner can manage the programs allowed to drive games.
+    AddTrustedProgram {
+        program_id: ActorId,
+    },
+    RemoveTrustedProgram {
+        program_id: ActorId,
+    },
+    // Replaces the bank of words, only the owner can do it.
+    UpdateBank {
+        words: Vec<String>,
+    },
+    // Reveals one letter of the word that no guess or earlier hint has found in its position.
+    RevealLetter {
+        user: ActorId,
+        session_id: MessageId,
+    },
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum GameMode {
+    #[default]
+    Classic,
+    // Every player gets the same word for the current epoch and can play it only once.
+    Daily,
+    // All games of the same room started by the same caller share one word.
+    Race {
+        room_id: u64,
+    },
+    // No word is fixed: each guess keeps the largest group of bank words matching all feedback,
+    // and the commitment covers the whole bank instead of a single word.
+    Absurdle,
 }
 
 #[derive(Debug, Clone, Encode, Decode, TypeInfo)]
 pub enum Event {
     GameStarted {
         user: ActorId,
+        session_id: MessageId,
+        commitment: [u8; 32],
     },
     WordChecked {
         user: ActorId,
+        session_id: MessageId,
         correct_positions: Vec<u8>,
         contained_in_word: Vec<u8>,
     },
-}
\ No newline at end of file
+    // The guess ignores hints revealed earlier in a hard mode game and was not scored.
+    HardModeViolation {
+        user: ActorId,
+        session_id: MessageId,
+    },
+    WordRevealed {
+        user: ActorId,
+        session_id: MessageId,
+        word: String,
+        salt: [u8; 32],
+        board: Vec<Guess>,
+        // The bank an Absurdle game was committed to, empty in the other modes.
+        bank: Vec<String>,
+    },
+    TrustedProgramAdded {
+        program_id: ActorId,
+    },
+    TrustedProgramRemoved {
+        program_id: ActorId,
+    },
+    BankUpdated {
+        words: u32,
+    },
+    // `letter` is the ASCII code of the letter at `position`.
+    LetterRevealed {
+        user: ActorId,
+        session_id: MessageId,
+        position: u8,
+        letter: u8,
+    },
+}
+
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub struct Guess {
+    pub word: String,
+    pub correct_positions: Vec<u8>,
+    pub contained_in_word: Vec<u8>,
+}
+
+// Hash of the secret word and its salt, announced in `Event::GameStarted`.
+pub fn commitment(word: &str, salt: &[u8; 32]) -> [u8; 32] {
+    Blake2b::<U32>::new()
+        .chain_update(word.as_bytes())
+        .chain_update(salt)
+        .finalize()
+        .into()
+}
+
+// Lets a player check off-chain that the revealed word is the one committed to at the start.
+pub fn verify_commitment(commitment: &[u8; 32], word: &str, salt: &[u8; 32]) -> bool {
+    self::commitment(word, salt) == *commitment
+}
+
+// The commitment of an Absurdle game covers the words of its bank joined in order.
+pub fn verify_bank_commitment(commitment: &[u8; 32], bank: &[String], salt: &[u8; 32]) -> bool {
+    verify_commitment(commitment, &bank.concat(), salt)
+}
+
+// Secret words never appear in the state, only public information about the games.
+#[derive(Debug, Default, Clone, Encode, Decode, TypeInfo)]
+pub struct WordleState {
+    pub active_games: u32,
+    pub games: Vec<(ActorId, GameInfo)>,
+    pub bank: BankStats,
+    pub config: WordleConfig,
+    pub daily_epoch: u32,
+    // Sorted by the fewest tries, then by the earliest finish.
+    pub daily_leaderboard: Vec<DailyResult>,
+    // `(epoch, leaderboard)` of the last played epochs before the current one, the oldest first.
+    pub past_daily_leaderboards: Vec<(u32, Vec<DailyResult>)>,
+}
+
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub struct DailyResult {
+    pub user: ActorId,
+    // The guesses and the hints it took to find the word.
+    pub tries: u8,
+    pub finished_at: u32,
+}
+
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub struct GameInfo {
+    pub session_id: MessageId,
+    pub mode: GameMode,
+    pub hard_mode: bool,
+    pub started_at: u32,
+    pub tries: u8,
+    pub commitment: [u8; 32],
+}
+
+#[derive(Debug, Default, Clone, Encode, Decode, TypeInfo)]
+pub struct BankStats {
+    pub words: u32,
+    pub games_started: u64,
+    pub games_won: u64,
+}
+
+#[derive(Debug, Default, Clone, Encode, Decode, TypeInfo)]
+pub struct WordleConfig {
+    pub owner: ActorId,
+    pub trusted_programs: Vec<ActorId>,
+    pub tries_limit: u8,
+    pub word_length: u8,
+    pub daily_epoch_blocks: u32,
+}
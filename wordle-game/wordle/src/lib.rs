@@ -1,21 +1,225 @@
 #![no_std]
-use gstd::{collections::HashMap, exec, msg, prelude::*, ActorId};
+use gstd::{
+    collections::{HashMap, HashSet},
+    exec, msg,
+    prelude::*,
+    ActorId, MessageId,
+};
 use wordle_io::*;
 
 static mut WORDLE: Option<Wordle> = None;
 
 const BANK_OF_WORDS: [&str; 3] = ["house", "human", "horse"];
 
+// Enforced here as well, so a buggy session program cannot grant extra tries.
+const TRIES_LIMIT: u8 = 6;
+
+// About one day with 3 second blocks.
+const DAILY_EPOCH_BLOCKS: u32 = 28_800;
+
+// Leaderboards of past epochs kept in the state, about a month of them.
+const DAILY_HISTORY_EPOCHS: usize = 30;
+
+// `(correct positions, positions contained in the word)` of a guess.
+type Feedback = (Vec<u8>, Vec<u8>);
+
 #[derive(Default)]
 struct Wordle {
-    games: HashMap<ActorId, String>,
+    owner: ActorId,
+    trusted_programs: HashSet<ActorId>,
+    bank: Vec<String>,
+    games: HashMap<(ActorId, MessageId), Game>,
+    daily: Daily,
+    // Leaderboards of the last `DAILY_HISTORY_EPOCHS` played epochs, the oldest first.
+    past_dailies: Vec<(u32, Vec<DailyResult>)>,
+    races: HashMap<(ActorId, u64), String>,
+    games_started: u64,
+    games_won: u64,
+}
+
+#[derive(Default)]
+struct Daily {
+    epoch: u32,
+    word: String,
+    players: HashSet<ActorId>,
+    leaderboard: Vec<DailyResult>,
+}
+
+struct Game {
+    word: String,
+    salt: [u8; 32],
+    commitment: [u8; 32],
+    // Words still consistent with every feedback given in Absurdle mode.
+    candidates: Vec<String>,
+    // The bank an Absurdle game was committed to.
+    bank: Vec<String>,
+    mode: GameMode,
+    hard_mode: bool,
+    started_at: u32,
+    guesses: Vec<Guess>,
+    // Positions revealed by `RevealLetter`.
+    hints: Vec<u8>,
+}
+
+impl Wordle {
+    // The daily word rotates by itself once the block height enters a new epoch.
+    fn daily(&mut self) -> &mut Daily {
+        let epoch = exec::block_height() / DAILY_EPOCH_BLOCKS;
+        if self.daily.word.is_empty() || self.daily.epoch != epoch {
+            if !self.daily.word.is_empty() {
+                if self.past_dailies.len() == DAILY_HISTORY_EPOCHS {
+                    self.past_dailies.remove(0);
+                }
+                let leaderboard = core::mem::take(&mut self.daily.leaderboard);
+                self.past_dailies.push((self.daily.epoch, leaderboard));
+            }
+            self.daily = Daily {
+                epoch,
+                word: get_random_word(&self.bank),
+                ..Default::default()
+            };
+        }
+        &mut self.daily
+    }
+
+    // A win counts for the epoch its game started in, even once that epoch is over, as long as
+    // its leaderboard is still kept.
+    fn record_daily_win(&mut self, epoch: u32, user: ActorId, tries: u8) {
+        let leaderboard = if epoch == self.daily.epoch {
+            &mut self.daily.leaderboard
+        } else if let Some((_, leaderboard)) = self
+            .past_dailies
+            .iter_mut()
+            .find(|(past_epoch, _)| *past_epoch == epoch)
+        {
+            leaderboard
+        } else {
+            return;
+        };
+        // Results arrive in finish order, so ties on tries keep the earliest finish first.
+        let position = leaderboard.partition_point(|result| result.tries <= tries);
+        leaderboard.insert(
+            position,
+            DailyResult {
+                user,
+                tries,
+                finished_at: exec::block_height(),
+            },
+        );
+    }
+}
+
+impl From<&Wordle> for WordleState {
+    fn from(wordle: &Wordle) -> Self {
+        Self {
+            active_games: wordle.games.len() as u32,
+            games: wordle
+                .games
+                .iter()
+                .map(|((user, session_id), game)| (*user, game.info(*session_id)))
+                .collect(),
+            bank: BankStats {
+                words: wordle.bank.len() as u32,
+                games_started: wordle.games_started,
+                games_won: wordle.games_won,
+            },
+            config: WordleConfig {
+                owner: wordle.owner,
+                trusted_programs: wordle.trusted_programs.iter().copied().collect(),
+                tries_limit: TRIES_LIMIT,
+                word_length: 5,
+                daily_epoch_blocks: DAILY_EPOCH_BLOCKS,
+            },
+            daily_epoch: wordle.daily.epoch,
+            daily_leaderboard: wordle.daily.leaderboard.clone(),
+            past_daily_leaderboards: wordle.past_dailies.clone(),
+        }
+    }
+}
+
+impl Game {
+    fn info(&self, session_id: MessageId) -> GameInfo {
+        GameInfo {
+            session_id,
+            mode: self.mode,
+            hard_mode: self.hard_mode,
+            started_at: self.started_at,
+            tries: self.guesses.len() as u8,
+            commitment: self.commitment,
+        }
+    }
+
+    // Started the same way in the current epoch and not played yet.
+    fn is_unplayed(&self, mode: GameMode, hard_mode: bool) -> bool {
+        self.mode == mode
+            && self.hard_mode == hard_mode
+            && self.guesses.is_empty()
+            && self.hints.is_empty()
+            && self.started_at / DAILY_EPOCH_BLOCKS == exec::block_height() / DAILY_EPOCH_BLOCKS
+    }
+
+    // In hard mode a guess must keep every letter found or revealed in its position
+    // and include every letter reported as contained in the word.
+    fn respects_hints(&self, word: &str) -> bool {
+        let word = word.as_bytes();
+        let secret = self.word.as_bytes();
+        self.hints
+            .iter()
+            .all(|&i| word[i as usize] == secret[i as usize])
+            && self.guesses.iter().all(|guess| {
+                let previous = guess.word.as_bytes();
+                guess
+                    .correct_positions
+                    .iter()
+                    .all(|&i| word[i as usize] == previous[i as usize])
+                    && guess
+                        .contained_in_word
+                        .iter()
+                        .all(|&i| word.contains(&previous[i as usize]))
+            })
+    }
+
+    // Positions no guess has found and no hint has revealed yet.
+    fn hidden_positions(&self) -> Vec<u8> {
+        (0..5)
+            .filter(|i| {
+                !self.hints.contains(i)
+                    && self
+                        .guesses
+                        .iter()
+                        .all(|guess| !guess.correct_positions.contains(i))
+            })
+            .collect()
+    }
+
+    // Splits the candidates by the feedback they would give and keeps the largest group,
+    // a solved group is only kept when nothing else is left.
+    fn narrow_candidates(&mut self, word: &str) -> Feedback {
+        let mut groups: Vec<(Feedback, Vec<String>)> = Vec::new();
+        for candidate in &self.candidates {
+            let feedback = check_word(candidate, word);
+            match groups.iter_mut().find(|(group, _)| *group == feedback) {
+                Some((_, words)) => words.push(candidate.clone()),
+                None => groups.push((feedback, vec![candidate.clone()])),
+            }
+        }
+        let (feedback, candidates) = groups
+            .into_iter()
+            .max_by_key(|((matched, _), words)| (words.len(), matched.len() < 5))
+            .expect("There are no candidates left");
+        self.word = candidates[0].clone();
+        self.candidates = candidates;
+        feedback
+    }
 }
 
 #[no_mangle]
 extern fn init() {
     unsafe {
         WORDLE = Some(Wordle {
-            games: HashMap::new(),
+            owner: msg::source(),
+            bank: BANK_OF_WORDS.iter().map(|word| word.to_string()).collect(),
+            ..Default::default()
         });
     }
 }
@@ -25,35 +229,196 @@ extern fn handle() {
     let action: Action = msg::load().expect("Unable to decode ");
     let wordle = unsafe { WORDLE.as_mut().expect("The program is not initialized") };
 
+    match action {
+        Action::AddTrustedProgram { .. }
+        | Action::RemoveTrustedProgram { .. }
+        | Action::UpdateBank { .. } => {
+            assert_eq!(
+                msg::source(),
+                wordle.owner,
+                "Only the owner can configure the program"
+            );
+        }
+        _ => {
+            assert!(
+                wordle.trusted_programs.contains(&msg::source()),
+                "Only trusted programs can drive games"
+            );
+        }
+    }
+
     let reply = match action {
-        Action::StartGame { user } => {
-            let random_id = get_random_value(BANK_OF_WORDS.len() as u8);
-            let word = BANK_OF_WORDS[random_id as usize];
-            wordle.games.insert(user, word.to_string());
-            Event::GameStarted { user }
+        Action::StartGame {
+            user,
+            session_id,
+            mode,
+            hard_mode,
+        } => {
+            // A start sent again, because the reply to the first one came too late, gets the
+            // same game back as long as nothing has been played in it.
+            if let Some(game) = wordle
+                .games
+                .get(&(user, session_id))
+                .filter(|game| game.is_unplayed(mode, hard_mode))
+            {
+                Event::GameStarted {
+                    user,
+                    session_id,
+                    commitment: game.commitment,
+                }
+            } else {
+                let mut candidates = Vec::new();
+                let word = match mode {
+                    GameMode::Classic => get_random_word(&wordle.bank),
+                    GameMode::Daily => {
+                        let daily = wordle.daily();
+                        assert!(
+                            daily.players.insert(user),
+                            "The user has already played the daily word"
+                        );
+                        daily.word.clone()
+                    }
+                    GameMode::Race { room_id } => wordle
+                        .races
+                        .entry((msg::source(), room_id))
+                        .or_insert_with(|| get_random_word(&wordle.bank))
+                        .clone(),
+                    GameMode::Absurdle => {
+                        candidates = wordle.bank.clone();
+                        candidates.concat()
+                    }
+                };
+                let salt = get_random_hash();
+                let commitment = commitment(&word, &salt);
+                wordle.games.insert(
+                    (user, session_id),
+                    Game {
+                        word: candidates.first().cloned().unwrap_or(word),
+                        salt,
+                        commitment,
+                        bank: candidates.clone(),
+                        candidates,
+                        mode,
+                        hard_mode,
+                        started_at: exec::block_height(),
+                        guesses: Vec::new(),
+                        hints: Vec::new(),
+                    },
+                );
+                wordle.games_started += 1;
+                Event::GameStarted {
+                    user,
+                    session_id,
+                    commitment,
+                }
+            }
         }
-        Action::CheckWord { user, word } => {
+        Action::CheckWord {
+            user,
+            session_id,
+            word,
+        } => {
             if word.len() != 5 {
                 panic!("The length of the word exceeds 5");
             }
-            let key_word = wordle
+            let game = wordle
                 .games
-                .get(&user)
+                .get_mut(&(user, session_id))
                 .expect("There is no game with this user");
-            let mut matched_indices = Vec::with_capacity(5);
-            let mut key_indices = Vec::with_capacity(5);
-            for (i, (a, b)) in key_word.chars().zip(word.chars()).enumerate() {
-                if a == b {
-                    matched_indices.push(i as u8);
-                } else if key_word.contains(b) {
-                    key_indices.push(i as u8);
+            assert!(
+                game.guesses.len() < TRIES_LIMIT as usize,
+                "The user has no tries left"
+            );
+            if game.hard_mode && !game.respects_hints(&word) {
+                Event::HardModeViolation { user, session_id }
+            } else {
+                let (matched_indices, key_indices) = if game.mode == GameMode::Absurdle {
+                    game.narrow_candidates(&word)
+                } else {
+                    check_word(&game.word, &word)
+                };
+                game.guesses.push(Guess {
+                    word,
+                    correct_positions: matched_indices.clone(),
+                    contained_in_word: key_indices.clone(),
+                });
+                if matched_indices.len() == 5 {
+                    wordle.games_won += 1;
+                    if game.mode == GameMode::Daily {
+                        let epoch = game.started_at / DAILY_EPOCH_BLOCKS;
+                        // A hint counts as a try, so a hinted win does not outrank the others.
+                        let tries = (game.guesses.len() + game.hints.len()) as u8;
+                        wordle.record_daily_win(epoch, user, tries);
+                    }
                 }
-            }
 
-            Event::WordChecked {
+                Event::WordChecked {
+                    user,
+                    session_id,
+                    correct_positions: matched_indices,
+                    contained_in_word: key_indices,
+                }
+            }
+        }
+        Action::RevealWord { user, session_id } => {
+            // The game is over once its word is revealed, so it is removed.
+            let game = wordle
+                .games
+                .remove(&(user, session_id))
+                .expect("There is no game with this user");
+            Event::WordRevealed {
+                user,
+                session_id,
+                word: game.word,
+                salt: game.salt,
+                board: game.guesses,
+                bank: game.bank,
+            }
+        }
+        Action::AddTrustedProgram { program_id } => {
+            wordle.trusted_programs.insert(program_id);
+            Event::TrustedProgramAdded { program_id }
+        }
+        Action::RemoveTrustedProgram { program_id } => {
+            wordle.trusted_programs.remove(&program_id);
+            Event::TrustedProgramRemoved { program_id }
+        }
+        Action::UpdateBank { words } => {
+            assert!(
+                !words.is_empty()
+                    && words
+                        .iter()
+                        .all(|word| word.len() == 5 && word.chars().all(|c| c.is_ascii_lowercase())),
+                "Invalid bank of words"
+            );
+            wordle.bank = words;
+            Event::BankUpdated {
+                words: wordle.bank.len() as u32,
+            }
+        }
+        Action::RevealLetter { user, session_id } => {
+            let game = wordle
+                .games
+                .get_mut(&(user, session_id))
+                .expect("There is no game with this user");
+            // The word of an Absurdle game is not fixed until the end, so no letter can be given.
+            assert!(
+                game.mode != GameMode::Absurdle,
+                "Hints are not available in Absurdle mode"
+            );
+            let hidden_positions = game.hidden_positions();
+            assert!(
+                !hidden_positions.is_empty(),
+                "Every letter is already revealed"
+            );
+            let position =
+                hidden_positions[get_random_value(hidden_positions.len() as u8) as usize];
+            game.hints.push(position);
+            Event::LetterRevealed {
                 user,
-                correct_positions: matched_indices,
-                contained_in_word: key_indices,
+                session_id,
+                position,
+                letter: game.word.as_bytes()[position as usize],
             }
         }
     };
@@ -61,13 +426,42 @@ extern fn handle() {
     msg::reply(reply, 0).expect("Error in sending a reply");
 }
 
+#[no_mangle]
+extern fn state() {
+    let wordle = unsafe { WORDLE.as_ref().expect("The program is not initialized") };
+    msg::reply::<WordleState>(wordle.into(), 0).expect("Failed to share state");
+}
+
+fn check_word(key_word: &str, word: &str) -> Feedback {
+    let mut matched_indices = Vec::with_capacity(5);
+    let mut key_indices = Vec::with_capacity(5);
+    for (i, (a, b)) in key_word.chars().zip(word.chars()).enumerate() {
+        if a == b {
+            matched_indices.push(i as u8);
+        } else if key_word.contains(b) {
+            key_indices.push(i as u8);
+        }
+    }
+    (matched_indices, key_indices)
+}
+
+fn get_random_word(bank: &[String]) -> String {
+    let random = get_random_hash();
+    let random_id = u32::from_le_bytes([random[0], random[1], random[2], random[3]]);
+    bank[random_id as usize % bank.len()].clone()
+}
+
 static mut SEED: u8 = 0;
 
 pub fn get_random_value(range: u8) -> u8 {
+    get_random_hash()[0] % range
+}
+
+fn get_random_hash() -> [u8; 32] {
     let seed = unsafe { SEED };
     unsafe { SEED = SEED.wrapping_add(1) };
     let mut random_input: [u8; 32] = exec::program_id().into();
     random_input[0] = random_input[0].wrapping_add(seed);
     let (random, _) = exec::random(random_input).expect("Error in getting random number");
-    random[0] % range
-}
\ No newline at end of file
+    random
+}
@@ -1,6 +1,10 @@
 use gstd::prelude::*;
 use gtest::{Log, Program, System};
-use wordle_io::{Action, Event};
+use wordle_io::{verify_bank_commitment, verify_commitment, Action, Event, GameMode, WordleState};
+
+// Games are keyed by the session that started them, any distinct ids do here.
+const SESSION_ID: [u8; 32] = [1; 32];
+const OTHER_SESSION_ID: [u8; 32] = [2; 32];
 
 #[test]
 fn test_start_game() {
@@ -14,7 +18,24 @@ fn test_start_game() {
 
     assert!(!result.main_failed(), "Program failed: {:?}", result);
 
-    let start_game_result = program.send(2, Action::StartGame { user: 2.into() });
+    let result = program.send(
+        2,
+        Action::AddTrustedProgram {
+            program_id: 2.into(),
+        },
+    );
+
+    assert!(!result.main_failed(), "Program failed: {:?}", result);
+
+    let start_game_result = program.send(
+        2,
+        Action::StartGame {
+            user: 2.into(),
+            session_id: SESSION_ID.into(),
+            mode: GameMode::Classic,
+            hard_mode: false,
+        },
+    );
 
     assert!(
         !start_game_result.main_failed(),
@@ -22,7 +43,33 @@ fn test_start_game() {
         start_game_result
     );
 
-    start_game_result.contains(&Log::builder().payload(Action::StartGame { user: 2.into() }));
+    start_game_result.contains(&Log::builder().payload(Action::StartGame {
+        user: 2.into(),
+        session_id: SESSION_ID.into(),
+        mode: GameMode::Classic,
+        hard_mode: false,
+    }));
+
+    let state: WordleState = program.read_state(b"").unwrap();
+    assert_eq!(state.active_games, 1);
+    assert_eq!(state.games[0].0, 2.into());
+    assert_eq!(state.games[0].1.tries, 0);
+    assert_eq!(state.bank.games_started, 1);
+
+    // A new session of the same user gets its own game
+    let start_game_result = program.send(
+        2,
+        Action::StartGame {
+            user: 2.into(),
+            session_id: OTHER_SESSION_ID.into(),
+            mode: GameMode::Classic,
+            hard_mode: false,
+        },
+    );
+    assert!(!start_game_result.main_failed());
+    let state: WordleState = program.read_state(b"").unwrap();
+    assert_eq!(state.active_games, 2);
+    assert_eq!(state.bank.games_started, 2);
 }
 #[test]
 fn test_wordle_game_success() {
@@ -36,7 +83,24 @@ fn test_wordle_game_success() {
 
     assert!(!result.main_failed(), "Program failed: {:?}", result);
 
-    let start_game_result = program.send(2, Action::StartGame { user: 2.into() });
+    let result = program.send(
+        2,
+        Action::AddTrustedProgram {
+            program_id: 2.into(),
+        },
+    );
+
+    assert!(!result.main_failed(), "Program failed: {:?}", result);
+
+    let start_game_result = program.send(
+        2,
+        Action::StartGame {
+            user: 2.into(),
+            session_id: SESSION_ID.into(),
+            mode: GameMode::Classic,
+            hard_mode: false,
+        },
+    );
 
     assert!(
         !start_game_result.main_failed(),
@@ -45,13 +109,19 @@ fn test_wordle_game_success() {
     );
 
     assert!(
-        start_game_result.contains(&Log::builder().payload(Action::StartGame { user: 2.into() }))
+        start_game_result.contains(&Log::builder().payload(Action::StartGame {
+            user: 2.into(),
+            session_id: SESSION_ID.into(),
+            mode: GameMode::Classic,
+            hard_mode: false,
+        }))
     );
 
     let wordle_result = program.send(
         2,
         Action::CheckWord {
             user: 2.into(),
+            session_id: SESSION_ID.into(),
             word: "house".to_string(),
         },
     );
@@ -63,6 +133,7 @@ fn test_wordle_game_success() {
     assert!(
         wordle_result.contains(&Log::builder().payload(Event::WordChecked {
             user: 2.into(),
+            session_id: SESSION_ID.into(),
             correct_positions: vec![0, 1, 2, 3, 4],
             contained_in_word: vec![],
         }))
@@ -83,7 +154,24 @@ fn test_wordle_game_more_times() {
 
     assert!(!result.main_failed(), "Program failed: {:?}", result);
 
-    let start_game_result = program.send(2, Action::StartGame { user: 2.into() });
+    let result = program.send(
+        2,
+        Action::AddTrustedProgram {
+            program_id: 2.into(),
+        },
+    );
+
+    assert!(!result.main_failed(), "Program failed: {:?}", result);
+
+    let start_game_result = program.send(
+        2,
+        Action::StartGame {
+            user: 2.into(),
+            session_id: SESSION_ID.into(),
+            mode: GameMode::Classic,
+            hard_mode: false,
+        },
+    );
 
     assert!(
         !start_game_result.main_failed(),
@@ -92,13 +180,19 @@ fn test_wordle_game_more_times() {
     );
 
     assert!(
-        start_game_result.contains(&Log::builder().payload(Action::StartGame { user: 2.into() }))
+        start_game_result.contains(&Log::builder().payload(Action::StartGame {
+            user: 2.into(),
+            session_id: SESSION_ID.into(),
+            mode: GameMode::Classic,
+            hard_mode: false,
+        }))
     );
 
     let wordle_result = program.send(
         2,
         Action::CheckWord {
             user: 2.into(),
+            session_id: SESSION_ID.into(),
             word: "human".to_string(),
         },
     );
@@ -112,6 +206,7 @@ fn test_wordle_game_more_times() {
     assert!(
         wordle_result.contains(&Log::builder().payload(Event::WordChecked {
             user: 2.into(),
+            session_id: SESSION_ID.into(),
             correct_positions: vec![0],
             contained_in_word: vec![1],
         }))
@@ -121,6 +216,7 @@ fn test_wordle_game_more_times() {
         2,
         Action::CheckWord {
             user: 2.into(),
+            session_id: SESSION_ID.into(),
             word: "heuan".to_string(),
         },
     );
@@ -134,6 +230,7 @@ fn test_wordle_game_more_times() {
     assert!(
         wordle_result.contains(&Log::builder().payload(Event::WordChecked {
             user: 2.into(),
+            session_id: SESSION_ID.into(),
             correct_positions: vec![0, 2],
             contained_in_word: vec![1],
         }))
@@ -142,6 +239,7 @@ fn test_wordle_game_more_times() {
         2,
         Action::CheckWord {
             user: 2.into(),
+            session_id: SESSION_ID.into(),
             word: "house".to_string(),
         },
     );
@@ -155,8 +253,637 @@ fn test_wordle_game_more_times() {
     assert!(
         wordle_result.contains(&Log::builder().payload(Event::WordChecked {
             user: 2.into(),
+            session_id: SESSION_ID.into(),
+            correct_positions: vec![0, 1, 2, 3, 4],
+            contained_in_word: vec![],
+        }))
+    );
+}
+
+#[test]
+fn test_hard_mode() {
+    let system = System::new();
+
+    system.init_logger();
+
+    let program = Program::current_opt(&system);
+
+    let result = program.send_bytes(2, []);
+
+    assert!(!result.main_failed(), "Program failed: {:?}", result);
+
+    let result = program.send(
+        2,
+        Action::AddTrustedProgram {
+            program_id: 2.into(),
+        },
+    );
+
+    assert!(!result.main_failed(), "Program failed: {:?}", result);
+
+    let start_game_result = program.send(
+        2,
+        Action::StartGame {
+            user: 2.into(),
+            session_id: SESSION_ID.into(),
+            mode: GameMode::Classic,
+            hard_mode: true,
+        },
+    );
+
+    assert!(
+        !start_game_result.main_failed(),
+        "Program failed: {:?}",
+        start_game_result
+    );
+
+    // Every word in the bank starts with "h", so the first letter is always revealed
+    let wordle_result = program.send(
+        2,
+        Action::CheckWord {
+            user: 2.into(),
+            session_id: SESSION_ID.into(),
+            word: "hello".to_string(),
+        },
+    );
+    assert!(
+        !wordle_result.main_failed(),
+        "Program failed: {:?}",
+        wordle_result
+    );
+
+    // The revealed "h" is not kept in its position
+    let wordle_result = program.send(
+        2,
+        Action::CheckWord {
+            user: 2.into(),
+            session_id: SESSION_ID.into(),
+            word: "shore".to_string(),
+        },
+    );
+    assert!(
+        !wordle_result.main_failed(),
+        "Program failed: {:?}",
+        wordle_result
+    );
+    assert!(
+        wordle_result.contains(&Log::builder().payload(Event::HardModeViolation {
+            user: 2.into(),
+            session_id: SESSION_ID.into()
+        }))
+    );
+}
+
+#[test]
+fn test_daily_word() {
+    let system = System::new();
+
+    system.init_logger();
+
+    let program = Program::current_opt(&system);
+
+    let result = program.send_bytes(2, []);
+
+    assert!(!result.main_failed(), "Program failed: {:?}", result);
+
+    let result = program.send(
+        2,
+        Action::AddTrustedProgram {
+            program_id: 2.into(),
+        },
+    );
+
+    assert!(!result.main_failed(), "Program failed: {:?}", result);
+
+    // The third player only finishes once the epoch is over
+    for user in [2u64, 3, 4] {
+        let start_game_result = program.send(
+            2,
+            Action::StartGame {
+                user: user.into(),
+                session_id: SESSION_ID.into(),
+                mode: GameMode::Daily,
+                hard_mode: false,
+            },
+        );
+        assert!(
+            !start_game_result.main_failed(),
+            "Program failed: {:?}",
+            start_game_result
+        );
+    }
+
+    // A start sent again gets the unplayed game back instead of a new one
+    let start_game_result = program.send(
+        2,
+        Action::StartGame {
+            user: 2.into(),
+            session_id: SESSION_ID.into(),
+            mode: GameMode::Daily,
+            hard_mode: false,
+        },
+    );
+    assert!(
+        !start_game_result.main_failed(),
+        "Program failed: {:?}",
+        start_game_result
+    );
+    let state: WordleState = program.read_state(b"").unwrap();
+    assert_eq!(state.bank.games_started, 3);
+
+    // The daily word can be played only once per epoch
+    let start_game_result = program.send(
+        2,
+        Action::StartGame {
+            user: 2.into(),
+            session_id: OTHER_SESSION_ID.into(),
+            mode: GameMode::Daily,
+            hard_mode: false,
+        },
+    );
+    assert!(start_game_result.main_failed());
+
+    // Both players share the word, so the first to find it is the second one's answer too
+    let mut tries = 0;
+    let mut daily_word = "";
+    for word in ["house", "human", "horse"] {
+        tries += 1;
+        daily_word = word;
+        let wordle_result = program.send(
+            2,
+            Action::CheckWord {
+                user: 2.into(),
+                session_id: SESSION_ID.into(),
+                word: word.to_string(),
+            },
+        );
+        if wordle_result.contains(&Log::builder().payload(Event::WordChecked {
+            user: 2.into(),
+            session_id: SESSION_ID.into(),
+            correct_positions: vec![0, 1, 2, 3, 4],
+            contained_in_word: vec![],
+        })) {
+            let wordle_result = program.send(
+                2,
+                Action::CheckWord {
+                    user: 3.into(),
+                    session_id: SESSION_ID.into(),
+                    word: word.to_string(),
+                },
+            );
+            assert!(
+                wordle_result.contains(&Log::builder().payload(Event::WordChecked {
+                    user: 3.into(),
+                    session_id: SESSION_ID.into(),
+                    correct_positions: vec![0, 1, 2, 3, 4],
+                    contained_in_word: vec![],
+                }))
+            );
+            break;
+        }
+    }
+
+    let state: WordleState = program.read_state(b"").unwrap();
+    assert_eq!(state.daily_leaderboard.len(), 2);
+    assert_eq!(state.daily_leaderboard[0].user, 3.into());
+    assert_eq!(state.daily_leaderboard[0].tries, 1);
+    assert_eq!(state.daily_leaderboard[1].tries, tries);
+
+    // A new epoch starts an empty leaderboard and keeps the last one in the history
+    system.spend_blocks(state.config.daily_epoch_blocks);
+    let start_game_result = program.send(
+        2,
+        Action::StartGame {
+            user: 2.into(),
+            session_id: OTHER_SESSION_ID.into(),
+            mode: GameMode::Daily,
+            hard_mode: false,
+        },
+    );
+    assert!(
+        !start_game_result.main_failed(),
+        "Program failed: {:?}",
+        start_game_result
+    );
+    let new_state: WordleState = program.read_state(b"").unwrap();
+    assert_eq!(new_state.daily_epoch, state.daily_epoch + 1);
+    assert!(new_state.daily_leaderboard.is_empty());
+    assert_eq!(new_state.past_daily_leaderboards.len(), 1);
+    assert_eq!(new_state.past_daily_leaderboards[0].0, state.daily_epoch);
+    assert_eq!(new_state.past_daily_leaderboards[0].1.len(), 2);
+
+    // A win of a game started in the last epoch goes on that epoch's leaderboard, its hint
+    // counted as a try
+    let wordle_result = program.send(
+        2,
+        Action::RevealLetter {
+            user: 4.into(),
+            session_id: SESSION_ID.into(),
+        },
+    );
+    assert!(!wordle_result.main_failed());
+    let wordle_result = program.send(
+        2,
+        Action::CheckWord {
+            user: 4.into(),
+            session_id: SESSION_ID.into(),
+            word: daily_word.to_string(),
+        },
+    );
+    assert!(!wordle_result.main_failed());
+    let new_state: WordleState = program.read_state(b"").unwrap();
+    assert!(new_state.daily_leaderboard.is_empty());
+    assert_eq!(new_state.past_daily_leaderboards[0].1.len(), 3);
+    assert!(new_state.past_daily_leaderboards[0]
+        .1
+        .iter()
+        .any(|result| result.user == 4.into() && result.tries == 2));
+}
+
+#[test]
+fn test_commitment_reveal() {
+    let system = System::new();
+
+    system.init_logger();
+
+    let program = Program::current_opt(&system);
+
+    let result = program.send_bytes(2, []);
+
+    assert!(!result.main_failed(), "Program failed: {:?}", result);
+
+    let result = program.send(
+        2,
+        Action::AddTrustedProgram {
+            program_id: 2.into(),
+        },
+    );
+
+    assert!(!result.main_failed(), "Program failed: {:?}", result);
+
+    let start_game_result = program.send(
+        2,
+        Action::StartGame {
+            user: 2.into(),
+            session_id: SESSION_ID.into(),
+            mode: GameMode::Classic,
+            hard_mode: false,
+        },
+    );
+    assert!(
+        !start_game_result.main_failed(),
+        "Program failed: {:?}",
+        start_game_result
+    );
+    let commitment = match start_game_result.decoded_log::<Event>()[0].payload() {
+        Event::GameStarted { commitment, .. } => *commitment,
+        event => panic!("Unexpected event: {:?}", event),
+    };
+
+    let reveal_result = program.send(
+        2,
+        Action::RevealWord {
+            user: 2.into(),
+            session_id: SESSION_ID.into(),
+        },
+    );
+    assert!(
+        !reveal_result.main_failed(),
+        "Program failed: {:?}",
+        reveal_result
+    );
+    match reveal_result.decoded_log::<Event>()[0].payload() {
+        Event::WordRevealed {
+            word, salt, board, ..
+        } => {
+            assert!(verify_commitment(&commitment, word, salt));
+            assert!(!verify_commitment(&commitment, "abcde", salt));
+            assert!(board.is_empty());
+        }
+        event => panic!("Unexpected event: {:?}", event),
+    }
+
+    // The game is closed once its word has been revealed
+    let wordle_result = program.send(
+        2,
+        Action::CheckWord {
+            user: 2.into(),
+            session_id: SESSION_ID.into(),
+            word: "house".to_string(),
+        },
+    );
+    assert!(wordle_result.main_failed());
+}
+
+#[test]
+fn test_access_control() {
+    let system = System::new();
+
+    system.init_logger();
+
+    let program = Program::current_opt(&system);
+
+    let result = program.send_bytes(2, []);
+
+    assert!(!result.main_failed(), "Program failed: {:?}", result);
+
+    // Direct calls from programs that are not trusted are rejected
+    let result = program.send(
+        3,
+        Action::StartGame {
+            user: 2.into(),
+            session_id: SESSION_ID.into(),
+            mode: GameMode::Classic,
+            hard_mode: false,
+        },
+    );
+    assert!(result.main_failed());
+
+    // Only the owner can trust a program
+    let result = program.send(
+        3,
+        Action::AddTrustedProgram {
+            program_id: 3.into(),
+        },
+    );
+    assert!(result.main_failed());
+
+    let result = program.send(
+        2,
+        Action::AddTrustedProgram {
+            program_id: 3.into(),
+        },
+    );
+    assert!(!result.main_failed(), "Program failed: {:?}", result);
+
+    let result = program.send(
+        3,
+        Action::StartGame {
+            user: 2.into(),
+            session_id: SESSION_ID.into(),
+            mode: GameMode::Classic,
+            hard_mode: false,
+        },
+    );
+    assert!(!result.main_failed(), "Program failed: {:?}", result);
+
+    // The tries limit holds whatever the caller does
+    for _ in 0..6 {
+        let result = program.send(
+            3,
+            Action::CheckWord {
+                user: 2.into(),
+                session_id: SESSION_ID.into(),
+                word: "abcde".to_string(),
+            },
+        );
+        assert!(!result.main_failed(), "Program failed: {:?}", result);
+    }
+    let result = program.send(
+        3,
+        Action::CheckWord {
+            user: 2.into(),
+            session_id: SESSION_ID.into(),
+            word: "abcde".to_string(),
+        },
+    );
+    assert!(result.main_failed());
+
+    let result = program.send(
+        2,
+        Action::RemoveTrustedProgram {
+            program_id: 3.into(),
+        },
+    );
+    assert!(!result.main_failed(), "Program failed: {:?}", result);
+
+    let result = program.send(
+        3,
+        Action::RevealWord {
+            user: 2.into(),
+            session_id: SESSION_ID.into(),
+        },
+    );
+    assert!(result.main_failed());
+}
+
+#[test]
+fn test_absurdle() {
+    let system = System::new();
+
+    system.init_logger();
+
+    let program = Program::current_opt(&system);
+
+    let result = program.send_bytes(2, []);
+
+    assert!(!result.main_failed(), "Program failed: {:?}", result);
+
+    let result = program.send(
+        2,
+        Action::AddTrustedProgram {
+            program_id: 2.into(),
+        },
+    );
+
+    assert!(!result.main_failed(), "Program failed: {:?}", result);
+
+    // Bank words must be five lowercase letters
+    let result = program.send(
+        2,
+        Action::UpdateBank {
+            words: vec!["house".to_string(), "Horse".to_string()],
+        },
+    );
+    assert!(result.main_failed());
+
+    let start_game_result = program.send(
+        2,
+        Action::StartGame {
+            user: 2.into(),
+            session_id: SESSION_ID.into(),
+            mode: GameMode::Absurdle,
+            hard_mode: false,
+        },
+    );
+    assert!(
+        !start_game_result.main_failed(),
+        "Program failed: {:?}",
+        start_game_result
+    );
+    let commitment = match start_game_result.decoded_log::<Event>()[0].payload() {
+        Event::GameStarted { commitment, .. } => *commitment,
+        event => panic!("Unexpected event: {:?}", event),
+    };
+
+    // Every bank word gives a different feedback, so the guess is dodged
+    let wordle_result = program.send(
+        2,
+        Action::CheckWord {
+            user: 2.into(),
+            session_id: SESSION_ID.into(),
+            word: "house".to_string(),
+        },
+    );
+    assert!(
+        wordle_result.contains(&Log::builder().payload(Event::WordChecked {
+            user: 2.into(),
+            session_id: SESSION_ID.into(),
+            correct_positions: vec![0, 1, 3, 4],
+            contained_in_word: vec![],
+        }))
+    );
+
+    // Only "horse" is left and it cannot be dodged any more
+    let wordle_result = program.send(
+        2,
+        Action::CheckWord {
+            user: 2.into(),
+            session_id: SESSION_ID.into(),
+            word: "horse".to_string(),
+        },
+    );
+    assert!(
+        wordle_result.contains(&Log::builder().payload(Event::WordChecked {
+            user: 2.into(),
+            session_id: SESSION_ID.into(),
             correct_positions: vec![0, 1, 2, 3, 4],
             contained_in_word: vec![],
         }))
     );
-}
\ No newline at end of file
+
+    // The revealed bank matches the commitment and holds the word
+    let reveal_result = program.send(
+        2,
+        Action::RevealWord {
+            user: 2.into(),
+            session_id: SESSION_ID.into(),
+        },
+    );
+    match reveal_result.decoded_log::<Event>()[0].payload() {
+        Event::WordRevealed {
+            word, salt, bank, ..
+        } => {
+            assert!(verify_bank_commitment(&commitment, bank, salt));
+            assert!(!verify_commitment(&commitment, word, salt));
+            assert!(bank.contains(word));
+        }
+        event => panic!("Unexpected event: {:?}", event),
+    }
+}
+
+#[test]
+fn test_reveal_letter() {
+    let system = System::new();
+
+    system.init_logger();
+
+    let program = Program::current_opt(&system);
+
+    let result = program.send_bytes(2, []);
+
+    assert!(!result.main_failed(), "Program failed: {:?}", result);
+
+    let result = program.send(
+        2,
+        Action::AddTrustedProgram {
+            program_id: 2.into(),
+        },
+    );
+
+    assert!(!result.main_failed(), "Program failed: {:?}", result);
+
+    // A letter can only be revealed in a started game
+    let result = program.send(
+        2,
+        Action::RevealLetter {
+            user: 2.into(),
+            session_id: SESSION_ID.into(),
+        },
+    );
+    assert!(result.main_failed());
+
+    let start_game_result = program.send(
+        2,
+        Action::StartGame {
+            user: 2.into(),
+            session_id: SESSION_ID.into(),
+            mode: GameMode::Classic,
+            hard_mode: false,
+        },
+    );
+    assert!(
+        !start_game_result.main_failed(),
+        "Program failed: {:?}",
+        start_game_result
+    );
+
+    // Every word in the bank starts with "h", so the first letter is found by this guess
+    let wordle_result = program.send(
+        2,
+        Action::CheckWord {
+            user: 2.into(),
+            session_id: SESSION_ID.into(),
+            word: "hello".to_string(),
+        },
+    );
+    assert!(
+        !wordle_result.main_failed(),
+        "Program failed: {:?}",
+        wordle_result
+    );
+
+    // Each hint reveals another position, never the one already found
+    let mut revealed = Vec::new();
+    for _ in 0..4 {
+        let result = program.send(
+            2,
+            Action::RevealLetter {
+                user: 2.into(),
+                session_id: SESSION_ID.into(),
+            },
+        );
+        assert!(!result.main_failed(), "Program failed: {:?}", result);
+        let Some(Event::LetterRevealed {
+            position, letter, ..
+        }) = result
+            .log()
+            .iter()
+            .find_map(|log| Event::decode(&mut log.payload()).ok())
+        else {
+            panic!("No letter was revealed: {:?}", result);
+        };
+        assert_ne!(position, 0);
+        assert!(!revealed.iter().any(|(p, _)| *p == position));
+        revealed.push((position, letter));
+    }
+
+    // Nothing is left to reveal
+    let result = program.send(
+        2,
+        Action::RevealLetter {
+            user: 2.into(),
+            session_id: SESSION_ID.into(),
+        },
+    );
+    assert!(result.main_failed());
+
+    // The revealed letters are the letters of the word
+    let result = program.send(
+        2,
+        Action::RevealWord {
+            user: 2.into(),
+            session_id: SESSION_ID.into(),
+        },
+    );
+    let Some(Event::WordRevealed { word, .. }) = result
+        .log()
+        .iter()
+        .find_map(|log| Event::decode(&mut log.payload()).ok())
+    else {
+        panic!("The word was not revealed: {:?}", result);
+    };
+    for (position, letter) in revealed {
+        assert_eq!(word.as_bytes()[position as usize], letter);
+    }
+}